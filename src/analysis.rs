@@ -28,6 +28,68 @@ impl Analysis {
             ffi::TDICE_ANALYSIS_TYPE_NONE => AnalysisType::None,
         }
     }
+
+    /// Return the initial temperature.
+    #[inline]
+    pub fn initial_temperature(&self) -> f64 {
+        self.raw.InitialTemperature
+    }
+
+    /// Return the number of time steps in a time slot.
+    #[inline]
+    pub fn slot_length(&self) -> usize {
+        self.raw.SlotLength as usize
+    }
+
+    /// Return the duration of a time slot.
+    #[inline]
+    pub fn slot_time(&self) -> f64 {
+        self.raw.SlotTime
+    }
+
+    /// Return the duration of a time step.
+    #[inline]
+    pub fn step_time(&self) -> f64 {
+        self.raw.StepTime
+    }
+
+    /// Set the initial temperature.
+    #[inline]
+    pub fn set_initial_temperature(&mut self, value: f64) {
+        self.raw.InitialTemperature = value;
+    }
+
+    /// Switch to the steady-state analysis.
+    pub fn set_steady(&mut self) {
+        self.raw.AnalysisType = ffi::TDICE_ANALYSIS_TYPE_STEADY;
+        self.raw.StepTime = 1.0;
+        self.raw.SlotTime = 0.0;
+        self.raw.SlotLength = 1;
+    }
+
+    /// Switch to the transient analysis with the given durations of a time
+    /// step and a time slot.
+    ///
+    /// The slot time should be a multiple of the step time up to rounding
+    /// errors.
+    pub fn set_transient(&mut self, step_time: f64, slot_time: f64) -> Result<()> {
+        if !(step_time > 0.0) {
+            raise!("the step time should be positive");
+        }
+        if !(slot_time >= step_time) {
+            raise!("the slot time should not be less than the step time");
+        }
+        let ratio = slot_time / step_time;
+        let length = ratio.round();
+        if (ratio - length).abs() > 1e-9 * length {
+            raise!("the slot time should be a multiple of the step time");
+        }
+        self.raw.AnalysisType = ffi::TDICE_ANALYSIS_TYPE_TRANSIENT;
+        self.raw.StepTime = step_time;
+        self.raw.SlotTime = slot_time;
+        self.raw.SlotLength = length as ffi::Quantity_t;
+        Ok(())
+    }
 }
 
 impl Drop for Analysis {
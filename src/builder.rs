@@ -0,0 +1,470 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use temporary::Directory;
+
+use analysis::AnalysisType;
+use channel::{Channel, ChannelModel};
+use floorplan::Floorplan;
+use heat_sink::{HeatSink, HeatSinkModel};
+use identifier::{self, check};
+use layer::Layer;
+use material::Material;
+use output::{OutputInstant, OutputInstruction, OutputQuantity, OutputType};
+use stack::{self, StackElement};
+use system::System;
+use {Error, Raw, Result};
+
+/// A builder of stacks.
+///
+/// The builder assembles a stack description in Rust and hands it over to
+/// 3D-ICE, which results in the same system as the one obtained from the
+/// equivalent stack-description file. The materials referred to by layers,
+/// heat sinks, and channels are declared automatically. The elements of the
+/// stack are given from top to bottom, and the layers of dies from bottom to
+/// top. The geometric quantities are given in micrometers.
+///
+/// A builder can also be obtained from an existing system, which allows one
+/// to modify the system or to write it back into files.
+#[derive(Clone, Debug, Default)]
+pub struct StackBuilder {
+    materials: Vec<Material>,
+    heat_sink: Option<HeatSink>,
+    channel: Option<Channel>,
+    walls: Option<(f64, f64, f64)>,
+    layers: Vec<Layer>,
+    dies: Vec<(String, Vec<Layer>, usize)>,
+    dimensions: Option<(f64, f64, f64, f64)>,
+    elements: Vec<Element>,
+    solver: Option<Solver>,
+    output: Vec<OutputInstruction>,
+}
+
+#[derive(Clone, Debug)]
+enum Element {
+    Channel(String),
+    Die(String, String, Floorplan),
+    Layer(String, String),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Solver {
+    Steady(f64),
+    Transient(f64, f64, f64),
+}
+
+impl StackBuilder {
+    /// Create a builder.
+    #[inline]
+    pub fn new() -> StackBuilder {
+        StackBuilder::default()
+    }
+
+    /// Declare a material.
+    pub fn material(mut self, material: Material) -> Self {
+        self.materials.push(material);
+        self
+    }
+
+    /// Set the heat sink.
+    pub fn heat_sink(mut self, heat_sink: HeatSink) -> Self {
+        self.heat_sink = Some(heat_sink);
+        self
+    }
+
+    /// Set the channel shared by all channel elements.
+    ///
+    /// The flow rate of the coolant is given in cubic micrometers per second.
+    pub fn channel(mut self, channel: Channel) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    /// Declare a layer, which should have an identifier.
+    pub fn layer(mut self, layer: Layer) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    /// Declare a die given its layers ordered from bottom to top and the
+    /// position of the source layer in the list.
+    pub fn die(mut self, id: &str, layers: Vec<Layer>, source: usize) -> Self {
+        self.dies.push((id.to_string(), layers, source));
+        self
+    }
+
+    /// Set the length and width of the chip and of the cells.
+    pub fn dimensions(mut self, chip_length: f64, chip_width: f64, cell_length: f64,
+                      cell_width: f64) -> Self {
+
+        self.dimensions = Some((chip_length, chip_width, cell_length, cell_width));
+        self
+    }
+
+    /// Append a channel to the stack.
+    pub fn stack_channel(mut self, id: &str) -> Self {
+        self.elements.push(Element::Channel(id.to_string()));
+        self
+    }
+
+    /// Append a die to the stack given the identifier of a declared die.
+    ///
+    /// Only the identifiers, power values, and rectangles of the elements of
+    /// the floorplan are taken into account.
+    pub fn stack_die(mut self, id: &str, die: &str, floorplan: Floorplan) -> Self {
+        self.elements.push(Element::Die(id.to_string(), die.to_string(), floorplan));
+        self
+    }
+
+    /// Append a layer to the stack given the identifier of a declared layer.
+    pub fn stack_layer(mut self, id: &str, layer: &str) -> Self {
+        self.elements.push(Element::Layer(id.to_string(), layer.to_string()));
+        self
+    }
+
+    /// Request a steady-state analysis.
+    pub fn steady(mut self, initial_temperature: f64) -> Self {
+        self.solver = Some(Solver::Steady(initial_temperature));
+        self
+    }
+
+    /// Request a transient analysis.
+    pub fn transient(mut self, step_time: f64, slot_time: f64, initial_temperature: f64) -> Self {
+        self.solver = Some(Solver::Transient(step_time, slot_time, initial_temperature));
+        self
+    }
+
+    /// Append an output instruction.
+    pub fn output(mut self, instruction: OutputInstruction) -> Self {
+        self.output.push(instruction);
+        self
+    }
+
+    /// Build a system.
+    ///
+    /// 3D-ICE constructs its structures only by parsing files. Therefore, the
+    /// stack description and the floorplans are written into a temporary
+    /// directory, which requires write access to the file system, and parsed
+    /// as usual. The directory is removed once the system is built.
+    pub fn build(&self) -> Result<System> {
+        let directory = try!(Directory::new("threed_ice"));
+        let path = directory.path().join("stack.stk");
+        try!(self.write(&path));
+        System::new(&path)
+    }
+
+    /// Write a stack-description file.
+    ///
+    /// The floorplan of each die is written next to the file and named after
+    /// the file and the die, e.g., `stack_DIE1.flp` for `stack.stk`. The paths
+    /// of the floorplans are given in the file as they are derived from the
+    /// path of the file, since 3D-ICE resolves them with respect to the
+    /// current working directory. Therefore, the paths should not contain
+    /// double quotes.
+    pub fn write<T: AsRef<Path>>(&self, path: T) -> Result<()> {
+        let path = path.as_ref();
+        let stem = some!(path.file_stem().and_then(|stem| stem.to_str()),
+                         Error::InvalidPath(path.to_path_buf()));
+        let mut floorplans = vec![];
+        for element in &self.elements {
+            if let Element::Die(ref id, _, ref floorplan) = *element {
+                let path = path.with_file_name(format!("{}_{}.flp", stem, try!(check(id))));
+                if !path.to_str().map_or(false, identifier::is_path) {
+                    raise!(Error::InvalidPath(path));
+                }
+                let mut writer = BufWriter::new(try!(File::create(&path)));
+                try!(floorplan.write(&mut writer));
+                floorplans.push(path);
+            }
+        }
+        let mut writer = BufWriter::new(try!(File::create(path)));
+        self.write_stack(&mut writer, &floorplans)
+    }
+
+    fn write_stack<W: Write>(&self, writer: &mut W, floorplans: &[PathBuf]) -> Result<()> {
+        for material in &try!(self.collect_materials()) {
+            try!(check(&material.id));
+            try!(write!(writer, "material {}:\n   thermal conductivity {:?};\n   \
+                                 volumetric heat capacity {:?};\n\n",
+                        material.id, material.thermal_conductivity,
+                        material.volumetric_heat_capacity));
+        }
+
+        if let Some(ref heat_sink) = self.heat_sink {
+            try!(write_heat_sink(writer, heat_sink));
+        }
+        if let Some(ref channel) = self.channel {
+            try!(write_channel(writer, channel, self.walls));
+        }
+
+        for layer in &self.layers {
+            let id = match layer.id {
+                Some(ref id) => try!(check(id)),
+                _ => raise!("the layers declared outside dies should have identifiers"),
+            };
+            try!(write!(writer, "layer {}:\n   height {:?};\n   material {};\n\n",
+                        id, layer.height, try!(check(&layer.material.id))));
+        }
+
+        for &(ref id, ref layers, source) in &self.dies {
+            if source >= layers.len() {
+                raise!("the source layer of a die is out of range");
+            }
+            try!(writeln!(writer, "die {}:", try!(check(id))));
+            for (i, layer) in layers.iter().enumerate().rev() {
+                let kind = if i == source { "source" } else { "layer" };
+                try!(writeln!(writer, "   {} {:?} {};", kind, layer.height,
+                              try!(check(&layer.material.id))));
+            }
+            try!(writeln!(writer));
+        }
+
+        let (chip_length, chip_width, cell_length, cell_width) = match self.dimensions {
+            Some(dimensions) => dimensions,
+            _ => raise!("the dimensions are not specified"),
+        };
+        try!(write!(writer, "dimensions:\n   chip length {:?}, width {:?};\n   \
+                             cell length {:?}, width {:?};\n\n",
+                    chip_length, chip_width, cell_length, cell_width));
+
+        try!(writeln!(writer, "stack:"));
+        let mut floorplans = floorplans.iter();
+        for element in &self.elements {
+            match *element {
+                Element::Channel(ref id) => {
+                    try!(writeln!(writer, "   channel {};", try!(check(id))));
+                },
+                Element::Die(ref id, ref die, _) => {
+                    let path = match floorplans.next().and_then(|path| path.to_str()) {
+                        Some(path) => path,
+                        _ => raise!("the path of a floorplan is not valid"),
+                    };
+                    try!(writeln!(writer, "   die {} {} floorplan \"{}\";",
+                                  try!(check(id)), try!(check(die)), path));
+                },
+                Element::Layer(ref id, ref layer) => {
+                    try!(writeln!(writer, "   layer {} {};", try!(check(id)), try!(check(layer))));
+                },
+            }
+        }
+        try!(writeln!(writer));
+
+        match self.solver {
+            Some(Solver::Steady(initial_temperature)) => {
+                try!(write!(writer, "solver:\n   steady;\n   initial temperature {:?};\n",
+                            initial_temperature));
+            },
+            Some(Solver::Transient(step_time, slot_time, initial_temperature)) => {
+                try!(write!(writer, "solver:\n   transient step {:?}, slot {:?};\n   \
+                                     initial temperature {:?};\n",
+                            step_time, slot_time, initial_temperature));
+            },
+            _ => raise!("the analysis is not specified"),
+        }
+
+        if !self.output.is_empty() {
+            try!(write!(writer, "\noutput:\n"));
+            for instruction in &self.output {
+                try!(write_instruction(writer, instruction));
+            }
+        }
+
+        Ok(try!(writer.flush()))
+    }
+
+    fn collect_materials(&self) -> Result<Vec<Material>> {
+        let mut materials: Vec<Material> = vec![];
+        let mut candidates = self.materials.iter().collect::<Vec<_>>();
+        if let Some(ref heat_sink) = self.heat_sink {
+            candidates.extend(heat_sink.sink.iter().map(|part| &part.material));
+            candidates.extend(heat_sink.spreader.iter().map(|part| &part.material));
+        }
+        if let Some(ref channel) = self.channel {
+            candidates.push(&channel.wall_material);
+        }
+        candidates.extend(self.layers.iter().map(|layer| &layer.material));
+        for &(_, ref layers, _) in &self.dies {
+            candidates.extend(layers.iter().map(|layer| &layer.material));
+        }
+        for material in candidates {
+            match materials.iter().find(|other| other.id == material.id) {
+                Some(other) if other != material => {
+                    raise!("the same identifier is used for different materials");
+                },
+                Some(_) => {},
+                _ => materials.push(material.clone()),
+            }
+        }
+        Ok(materials)
+    }
+}
+
+impl<'l> From<&'l System> for StackBuilder {
+    /// Create a builder reproducing a system.
+    fn from(system: &'l System) -> StackBuilder {
+        let stack = &system.stack;
+        let mut builder = StackBuilder {
+            materials: stack.materials.clone(),
+            layers: stack.layers.clone(),
+            output: system.output.instructions.clone(),
+            ..StackBuilder::default()
+        };
+
+        let ids = unsafe { stack::extract_ids(stack.raw()) };
+        for (id, element) in ids.into_iter().zip(&stack.elements) {
+            match *element {
+                StackElement::Channel(ref channel) => {
+                    if channel.model == ChannelModel::Microchannel4RM {
+                        let cell = unsafe { &(*stack.raw().Dimensions).Cell };
+                        builder.walls = Some((cell.WallLength, cell.FirstWallLength,
+                                              cell.LastWallLength));
+                    }
+                    builder.channel = Some(channel.clone());
+                    builder.elements.push(Element::Channel(id));
+                },
+                StackElement::Die(ref die) => {
+                    if !builder.dies.iter().any(|&(ref other, _, _)| other == &die.id) {
+                        builder.dies.push((die.id.clone(), die.layers.clone(), die.source));
+                    }
+                    builder.elements.push(Element::Die(id, die.id.clone(), die.floorplan.clone()));
+                },
+                StackElement::HeatSink(ref heat_sink) => {
+                    builder.heat_sink = Some(heat_sink.clone());
+                },
+                StackElement::Layer(ref layer) => {
+                    let layer = layer.id.clone().unwrap_or_default();
+                    builder.elements.push(Element::Layer(id, layer));
+                },
+                StackElement::None => {},
+            }
+        }
+
+        // In the case of 4RM, the first column is a wall, and the cell length
+        // of the stack description is the one of the channels.
+        let dimensions = &stack.dimensions;
+        let column = if builder.walls.is_some() { 1 } else { 0 };
+        builder.dimensions = Some((dimensions.chip_length(), dimensions.chip_width(),
+                                   dimensions.cell_length(column), dimensions.cell_width()));
+
+        let analysis = &system.analysis;
+        let initial_temperature = analysis.initial_temperature();
+        builder.solver = match analysis.kind() {
+            AnalysisType::Steady => Some(Solver::Steady(initial_temperature)),
+            AnalysisType::Transient => {
+                Some(Solver::Transient(analysis.step_time(), analysis.slot_time(),
+                                       initial_temperature))
+            },
+            AnalysisType::None => None,
+        };
+
+        builder
+    }
+}
+
+fn write_channel<W: Write>(writer: &mut W, channel: &Channel,
+                           walls: Option<(f64, f64, f64)>) -> Result<()> {
+
+    let coolant = &channel.coolant;
+    let flow_rate = coolant.flow_rate * 60.0 / 1e12;
+    match channel.model {
+        ChannelModel::Microchannel4RM => {
+            let wall = channel.pitch - channel.length;
+            let (wall, first, last) = walls.unwrap_or((wall, wall, wall));
+            try!(write!(writer, "microchannel 4rm:\n   height {:?};\n   channel length {:?};\n   \
+                                 wall length {:?};\n   first wall length {:?};\n   \
+                                 last wall length {:?};\n   wall material {};\n   \
+                                 coolant flow rate {:?};\n   coolant heat transfer coefficient \
+                                 side {:?}, top {:?}, bottom {:?};\n",
+                        channel.height, channel.length, wall, first, last,
+                        try!(check(&channel.wall_material.id)), flow_rate,
+                        coolant.side_heat_transfer_coefficient,
+                        coolant.top_heat_transfer_coefficient,
+                        coolant.bottom_heat_transfer_coefficient));
+        },
+        ChannelModel::Microchannel2RM => {
+            try!(write!(writer, "microchannel 2rm:\n   height {:?};\n   channel length {:?};\n   \
+                                 wall length {:?};\n   wall material {};\n   \
+                                 coolant flow rate {:?};\n   coolant heat transfer coefficient \
+                                 top {:?}, bottom {:?};\n",
+                        channel.height, channel.length, channel.pitch - channel.length,
+                        try!(check(&channel.wall_material.id)), flow_rate,
+                        coolant.top_heat_transfer_coefficient,
+                        coolant.bottom_heat_transfer_coefficient));
+        },
+        ChannelModel::PinFinInline | ChannelModel::PinFinStaggered => {
+            let distribution = if channel.model == ChannelModel::PinFinInline {
+                "inline"
+            } else {
+                "staggered"
+            };
+            try!(write!(writer, "pinfin:\n   height {:?};\n   pin diameter {:?};\n   \
+                                 pin pitch {:?};\n   pin distribution {};\n   \
+                                 pin material {};\n   darcy velocity {:?};\n",
+                        channel.height, channel.length, channel.pitch, distribution,
+                        try!(check(&channel.wall_material.id)), coolant.darcy_velocity));
+        },
+        ChannelModel::None => raise!("the model of the channel is not specified"),
+    }
+    try!(write!(writer, "   coolant volumetric heat capacity {:?};\n   \
+                         coolant incoming temperature {:?};\n\n",
+                coolant.volumetric_heat_capacity, coolant.temperature));
+    Ok(())
+}
+
+fn write_heat_sink<W: Write>(writer: &mut W, heat_sink: &HeatSink) -> Result<()> {
+    match (heat_sink.model, &heat_sink.sink, &heat_sink.spreader) {
+        (HeatSinkModel::ConnectionToAmbient, _, _) => {
+            try!(writeln!(writer, "connection to ambient:"));
+        },
+        (HeatSinkModel::Traditional, &Some(ref sink), &Some(ref spreader)) => {
+            try!(write!(writer, "heat sink:\n   sink height {:?}, area {:?}, material {};\n   \
+                                 spreader height {:?}, area {:?}, material {};\n",
+                        sink.height, sink.area, try!(check(&sink.material.id)),
+                        spreader.height, spreader.area, try!(check(&spreader.material.id))));
+        },
+        (HeatSinkModel::Traditional, _, _) => {
+            raise!("the traditional heat sink should have a sink and a spreader");
+        },
+        (HeatSinkModel::None, _, _) => raise!("the model of the heat sink is not specified"),
+    }
+    try!(write!(writer, "   heat transfer coefficient {:?};\n   ambient temperature {:?};\n\n",
+                heat_sink.heat_transfer_coefficient, heat_sink.ambient_temperature));
+    Ok(())
+}
+
+fn write_instruction<W: Write>(writer: &mut W, instruction: &OutputInstruction) -> Result<()> {
+    let (element, file) = (try!(check(&instruction.element)), &instruction.file);
+    let instant = match instruction.instant {
+        OutputInstant::Final => ", final",
+        OutputInstant::Slot => ", slot",
+        OutputInstant::Step => ", step",
+        OutputInstant::None => "",
+    };
+    let quantity = match instruction.quantity {
+        OutputQuantity::Average => "average",
+        OutputQuantity::Maximum => "maximum",
+        OutputQuantity::Minimum => "minimum",
+        OutputQuantity::None => "",
+    };
+    let result = match instruction.kind {
+        OutputType::Tcell { x, y } => {
+            writeln!(writer, "   T({}, {:?}, {:?}, \"{}\"{});", element, x, y, file, instant)
+        },
+        OutputType::Tflp | OutputType::Tflpel(_) | OutputType::Tcoolant
+            if quantity.is_empty() => raise!("the output quantity is not specified"),
+        OutputType::Tflp => {
+            writeln!(writer, "   Tflp({}, \"{}\", {}{});", element, file, quantity, instant)
+        },
+        OutputType::Tflpel(ref id) => {
+            writeln!(writer, "   Tflpel({}.{}, \"{}\", {}{});", element, try!(check(id)), file,
+                     quantity, instant)
+        },
+        OutputType::Tmap => writeln!(writer, "   Tmap({}, \"{}\"{});", element, file, instant),
+        OutputType::Pmap => writeln!(writer, "   Pmap({}, \"{}\"{});", element, file, instant),
+        OutputType::Tcoolant => {
+            writeln!(writer, "   Tcoolant({}, \"{}\", {}{});", element, file, quantity, instant)
+        },
+        OutputType::None => raise!("the output type is not specified"),
+    };
+    Ok(try!(result))
+}
@@ -0,0 +1,99 @@
+use ffi;
+use std::f64::consts::PI;
+
+use material::{self, Material};
+
+/// A channel for liquid cooling.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Channel {
+    /// The model.
+    pub model: ChannelModel,
+    /// The height.
+    pub height: f64,
+    /// The length of a channel or the diameter of a pin.
+    pub length: f64,
+    /// The distance between the centers of two neighboring channels or pins.
+    pub pitch: f64,
+    /// The ratio between the volume of the coolant and the total volume.
+    pub porosity: f64,
+    /// The number of channels.
+    pub channels: usize,
+    /// The coolant.
+    pub coolant: Coolant,
+    /// The material of the walls or pins.
+    pub wall_material: Material,
+}
+
+/// A model of a channel.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChannelModel {
+    /// Microchannels modeled with four resistors.
+    Microchannel4RM,
+    /// Microchannels modeled with two resistors.
+    Microchannel2RM,
+    /// Pin fins arranged in line.
+    PinFinInline,
+    /// Pin fins arranged in a staggered manner.
+    PinFinStaggered,
+    /// An undefined model.
+    None,
+}
+
+/// A coolant.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Coolant {
+    /// The heat transfer coefficient of the side walls.
+    pub side_heat_transfer_coefficient: f64,
+    /// The heat transfer coefficient of the top wall.
+    pub top_heat_transfer_coefficient: f64,
+    /// The heat transfer coefficient of the bottom wall.
+    pub bottom_heat_transfer_coefficient: f64,
+    /// The volumetric heat capacity.
+    pub volumetric_heat_capacity: f64,
+    /// The flow rate in cubic micrometers per second.
+    pub flow_rate: f64,
+    /// The Darcy velocity.
+    pub darcy_velocity: f64,
+    /// The incoming temperature.
+    pub temperature: f64,
+}
+
+pub unsafe fn new(raw: &ffi::Channel_t, dimensions: &ffi::Dimensions_t) -> Channel {
+    let model = match raw.ChannelModel {
+        ffi::TDICE_CHANNEL_MODEL_MC_4RM => ChannelModel::Microchannel4RM,
+        ffi::TDICE_CHANNEL_MODEL_MC_2RM => ChannelModel::Microchannel2RM,
+        ffi::TDICE_CHANNEL_MODEL_PF_INLINE => ChannelModel::PinFinInline,
+        ffi::TDICE_CHANNEL_MODEL_PF_STAGGERED => ChannelModel::PinFinStaggered,
+        ffi::TDICE_CHANNEL_MODEL_NONE => ChannelModel::None,
+    };
+    let (length, pitch, porosity) = match model {
+        ChannelModel::Microchannel4RM => {
+            let cell = &dimensions.Cell;
+            let pitch = cell.ChannelLength + cell.WallLength;
+            (cell.ChannelLength, pitch, cell.ChannelLength / pitch)
+        },
+        ChannelModel::PinFinInline | ChannelModel::PinFinStaggered => {
+            let diameter = raw.Pitch * (4.0 * (1.0 - raw.Porosity) / PI).sqrt();
+            (diameter, raw.Pitch, raw.Porosity)
+        },
+        _ => (raw.Length, raw.Pitch, raw.Porosity),
+    };
+    Channel {
+        model: model,
+        height: raw.Height,
+        length: length,
+        pitch: pitch,
+        porosity: porosity,
+        channels: raw.NChannels as usize,
+        coolant: Coolant {
+            side_heat_transfer_coefficient: raw.Coolant.HTCSide,
+            top_heat_transfer_coefficient: raw.Coolant.HTCTop,
+            bottom_heat_transfer_coefficient: raw.Coolant.HTCBottom,
+            volumetric_heat_capacity: raw.Coolant.VHC,
+            flow_rate: raw.Coolant.FlowRate,
+            darcy_velocity: raw.Coolant.DarcyVelocity,
+            temperature: raw.Coolant.TIn,
+        },
+        wall_material: material::new(&raw.WallMaterial),
+    }
+}
@@ -0,0 +1,464 @@
+use ffi;
+use matrix::format::Compressed;
+use matrix::format::compressed::Variant;
+use std::{panic, thread};
+
+use analysis::{Analysis, AnalysisType};
+use stack::Stack;
+use thermal_grid::ThermalGrid;
+use {Raw, Result};
+
+/// A block of columns of the conductance matrix.
+///
+/// Each layer of the thermal grid contributes a block. The blocks are
+/// assembled in parallel and then concatenated to form the whole matrix.
+struct Block {
+    lengths: Vec<usize>,
+    indices: Vec<usize>,
+    values: Vec<f64>,
+}
+
+/// The position of a cell in the thermal grid.
+#[derive(Clone, Copy)]
+struct Cell {
+    layer: usize,
+    row: usize,
+    column: usize,
+}
+
+struct Context<'l> {
+    stack: &'l Stack,
+    grid: &'l ThermalGrid,
+    step_time: Option<f64>,
+    layers: usize,
+    rows: usize,
+    columns: usize,
+}
+
+// The context is shared by the threads assembling the blocks. The routines of
+// 3D-ICE called through the grid only read the grid and the dimensions of the
+// stack, and neither can be modified while the context borrows them.
+unsafe impl<'l> Sync for Context<'l> {}
+
+/// Assemble the conductance matrix, which includes `C/dt` on the diagonal
+/// in the case of transient analysis.
+///
+/// The matrix is the one that 3D-ICE builds for its solver, and it is stored
+/// in the compressed-column format with the entries of each column ordered by
+/// row.
+pub fn assemble(stack: &Stack, analysis: &Analysis, grid: &ThermalGrid)
+                -> Result<Compressed<f64>> {
+    let dimensions = stack.raw().Dimensions;
+    let context = unsafe {
+        Context {
+            stack: stack,
+            grid: grid,
+            step_time: match analysis.kind() {
+                AnalysisType::Transient => Some(analysis.step_time()),
+                _ => None,
+            },
+            layers: ffi::get_number_of_layers(dimensions) as usize,
+            rows: ffi::get_number_of_rows(dimensions) as usize,
+            columns: ffi::get_number_of_columns(dimensions) as usize,
+        }
+    };
+
+    let layers = (0..context.layers).collect::<Vec<_>>();
+    let workers = thread::available_parallelism().map_or(1, |count| count.get());
+    let chunk = ((layers.len() + workers - 1) / workers).max(1);
+    let blocks = thread::scope(|scope| {
+        let context = &context;
+        let handles = layers.chunks(chunk).map(|layers| {
+            scope.spawn(move || {
+                layers.iter().map(|&layer| context.block(layer)).collect::<Vec<_>>()
+            })
+        }).collect::<Vec<_>>();
+        handles.into_iter().flat_map(|handle| match handle.join() {
+            Ok(blocks) => blocks,
+            Err(error) => panic::resume_unwind(error),
+        }).collect::<Vec<_>>()
+    });
+    let blocks = try!(blocks.into_iter().collect::<Result<Vec<_>>>());
+
+    let size = context.layers * context.rows * context.columns;
+    let nonzeros = blocks.iter().fold(0, |sum, block| sum + block.values.len());
+    let mut offsets = Vec::with_capacity(size + 1);
+    let mut indices = Vec::with_capacity(nonzeros);
+    let mut values = Vec::with_capacity(nonzeros);
+    offsets.push(0);
+    for block in blocks {
+        for length in block.lengths {
+            let offset = offsets[offsets.len() - 1] + length;
+            offsets.push(offset);
+        }
+        indices.extend(block.indices);
+        values.extend(block.values);
+    }
+
+    Ok(Compressed {
+        rows: size,
+        columns: size,
+        nonzeros: nonzeros,
+        variant: Variant::Column,
+        values: values,
+        indices: indices,
+        offsets: offsets,
+    })
+}
+
+impl<'l> Context<'l> {
+    fn block(&self, layer: usize) -> Result<Block> {
+        use ffi::StackLayerType_t::*;
+
+        let profile = self.grid.profile(layer);
+        if profile == TDICE_LAYER_NONE {
+            raise!(format!("the layer {} of the thermal grid has no type", layer));
+        }
+        let mut block = Block { lengths: vec![], indices: vec![], values: vec![] };
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let cell = Cell { layer: layer, row: row, column: column };
+                let start = block.values.len();
+                match profile {
+                    TDICE_LAYER_CHANNEL_4RM if column % 2 == 1 => {
+                        self.add_liquid_4rm(&mut block, cell)
+                    },
+                    TDICE_LAYER_CHANNEL_2RM | TDICE_LAYER_PINFINS_INLINE |
+                    TDICE_LAYER_PINFINS_STAGGERED => self.add_liquid_2rm(&mut block, cell),
+                    TDICE_LAYER_VWALL_CHANNEL | TDICE_LAYER_VWALL_PINFINS => {
+                        self.add_virtual_wall(&mut block, cell)
+                    },
+                    TDICE_LAYER_TOP_WALL => self.add_top_wall(&mut block, cell),
+                    TDICE_LAYER_BOTTOM_WALL => self.add_bottom_wall(&mut block, cell),
+                    _ => self.add_solid(&mut block, cell),
+                }
+                block.lengths.push(block.values.len() - start);
+            }
+        }
+        Ok(block)
+    }
+
+    fn add_solid(&self, block: &mut Block, cell: Cell) {
+        use ffi::StackLayerType_t::*;
+
+        let Cell { layer, row, column } = cell;
+        let profile = self.grid.profile(layer);
+        let lateral = profile != TDICE_LAYER_SPREADER && profile != TDICE_LAYER_SINK;
+        let mut diagonal = 0.0;
+
+        if layer > 0 {
+            let bottom = self.grid.conductance_bottom(self.stack, layer, row, column);
+            let top = self.grid.conductance_top(self.stack, layer - 1, row, column);
+            let conductance = if top == 0.0 { bottom } else { parallel(bottom, top) };
+            diagonal += self.push(block, layer - 1, row, column, -conductance);
+        }
+        if lateral && row > 0 {
+            let conductance = parallel(
+                self.grid.conductance_south(self.stack, layer, row, column),
+                self.grid.conductance_north(self.stack, layer, row - 1, column));
+            diagonal += self.push(block, layer, row - 1, column, -conductance);
+        }
+        if lateral && column > 0 {
+            let conductance = parallel(
+                self.grid.conductance_west(self.stack, layer, row, column),
+                self.grid.conductance_east(self.stack, layer, row, column - 1));
+            diagonal += self.push(block, layer, row, column - 1, -conductance);
+        }
+
+        let mut own = self.capacity(cell);
+        if profile == TDICE_LAYER_SINK || profile == TDICE_LAYER_SOLID_CONNECTED_TO_AMBIENT ||
+           profile == TDICE_LAYER_SOURCE_CONNECTED_TO_AMBIENT {
+            own += self.grid.conductance_top(self.stack, layer, row, column);
+        }
+        let position = self.reserve(block, cell);
+
+        if lateral && column + 1 < self.columns {
+            let conductance = parallel(
+                self.grid.conductance_east(self.stack, layer, row, column),
+                self.grid.conductance_west(self.stack, layer, row, column + 1));
+            diagonal += self.push(block, layer, row, column + 1, -conductance);
+        }
+        if lateral && row + 1 < self.rows {
+            let conductance = parallel(
+                self.grid.conductance_north(self.stack, layer, row, column),
+                self.grid.conductance_south(self.stack, layer, row + 1, column));
+            diagonal += self.push(block, layer, row + 1, column, -conductance);
+        }
+        if layer + 1 < self.layers {
+            let top = self.grid.conductance_top(self.stack, layer, row, column);
+            let bottom = self.grid.conductance_bottom(self.stack, layer + 1, row, column);
+            let conductance = if bottom == 0.0 { top } else { parallel(top, bottom) };
+            diagonal += self.push(block, layer + 1, row, column, -conductance);
+        }
+
+        block.values[position] = own + diagonal;
+    }
+
+    fn add_liquid_4rm(&self, block: &mut Block, cell: Cell) {
+        let Cell { layer, row, column } = cell;
+        let mut diagonal = 0.0;
+
+        if layer > 0 {
+            let conductance = parallel(
+                self.grid.conductance_bottom(self.stack, layer, row, column),
+                self.grid.conductance_top(self.stack, layer - 1, row, column));
+            diagonal += self.push(block, layer - 1, row, column, -conductance);
+        }
+        if row > 0 {
+            let advection = self.grid.conductance_north(self.stack, layer, row, column);
+            self.push(block, layer, row - 1, column, advection);
+        }
+        if column > 0 {
+            let conductance = parallel(
+                self.grid.conductance_west(self.stack, layer, row, column),
+                self.grid.conductance_east(self.stack, layer, row, column - 1));
+            diagonal += self.push(block, layer, row, column - 1, -conductance);
+        }
+
+        let own = self.capacity(cell);
+        let position = self.reserve(block, cell);
+
+        if column + 1 < self.columns {
+            let conductance = parallel(
+                self.grid.conductance_east(self.stack, layer, row, column),
+                self.grid.conductance_west(self.stack, layer, row, column + 1));
+            diagonal += self.push(block, layer, row, column + 1, -conductance);
+        }
+        if row + 1 < self.rows {
+            let advection = self.grid.conductance_south(self.stack, layer, row, column);
+            self.push(block, layer, row + 1, column, advection);
+        }
+        if layer + 1 < self.layers {
+            let conductance = parallel(
+                self.grid.conductance_top(self.stack, layer, row, column),
+                self.grid.conductance_bottom(self.stack, layer + 1, row, column));
+            diagonal += self.push(block, layer + 1, row, column, -conductance);
+        }
+
+        block.values[position] = own + diagonal + self.inflow(cell);
+    }
+
+    fn add_liquid_2rm(&self, block: &mut Block, cell: Cell) {
+        let Cell { layer, row, column } = cell;
+        let mut diagonal = 0.0;
+
+        // The layers of a channel modeled with two resistors are, from bottom
+        // to top, the bottom wall, the virtual wall, the liquid, and the top
+        // wall, and some cells are connected across two layers. 3D-ICE checks
+        // only that the layer is not the first or last one. Since a channel can
+        // be neither the bottom-most nor the top-most element of a stack, the
+        // checks here are the same for every stack that 3D-ICE accepts, and
+        // they keep the indices within the grid otherwise.
+        if layer > 1 {
+            let conductance = self.grid.conductance_bottom(self.stack, layer, row, column);
+            diagonal += self.push(block, layer - 2, row, column, -conductance);
+        }
+        if row > 0 {
+            let advection = self.grid.conductance_north(self.stack, layer, row, column);
+            self.push(block, layer, row - 1, column, advection);
+        }
+
+        let own = self.capacity(cell);
+        let position = self.reserve(block, cell);
+
+        if row + 1 < self.rows {
+            let advection = self.grid.conductance_south(self.stack, layer, row, column);
+            self.push(block, layer, row + 1, column, advection);
+        }
+        if layer + 1 < self.layers {
+            let conductance = self.grid.conductance_top(self.stack, layer, row, column);
+            diagonal += self.push(block, layer + 1, row, column, -conductance);
+        }
+
+        block.values[position] = own + diagonal + self.inflow(cell);
+    }
+
+    fn add_virtual_wall(&self, block: &mut Block, cell: Cell) {
+        use ffi::ChannelModel_t::*;
+
+        let Cell { layer, row, column } = cell;
+        let model = unsafe { (*self.grid.raw().Channel).ChannelModel };
+        let lateral = model == TDICE_CHANNEL_MODEL_MC_2RM;
+        let mut diagonal = 0.0;
+
+        if layer > 0 {
+            let conductance = self.grid.conductance_bottom(self.stack, layer, row, column);
+            diagonal += self.push(block, layer - 1, row, column, -conductance);
+        }
+        if lateral && row > 0 {
+            let conductance = parallel(
+                self.grid.conductance_south(self.stack, layer, row, column),
+                self.grid.conductance_north(self.stack, layer, row, column));
+            diagonal += self.push(block, layer, row - 1, column, -conductance);
+        }
+
+        let own = self.capacity(cell);
+        let position = self.reserve(block, cell);
+
+        if lateral && row + 1 < self.rows {
+            let conductance = parallel(
+                self.grid.conductance_north(self.stack, layer, row, column),
+                self.grid.conductance_south(self.stack, layer, row, column));
+            diagonal += self.push(block, layer, row + 1, column, -conductance);
+        }
+        // See `add_liquid_2rm` for the difference from 3D-ICE.
+        if layer + 2 < self.layers {
+            let conductance = self.grid.conductance_top(self.stack, layer, row, column);
+            diagonal += self.push(block, layer + 2, row, column, -conductance);
+        }
+
+        block.values[position] = own + diagonal;
+    }
+
+    fn add_top_wall(&self, block: &mut Block, cell: Cell) {
+        let Cell { layer, row, column } = cell;
+        let mut diagonal = 0.0;
+
+        // See `add_liquid_2rm` for the difference from 3D-ICE.
+        if layer > 1 {
+            let conductance = self.grid.conductance_top(self.stack, layer - 2, row, column);
+            diagonal += self.push(block, layer - 2, row, column, -conductance);
+            let conductance = self.grid.conductance_top(self.stack, layer - 1, row, column);
+            diagonal += self.push(block, layer - 1, row, column, -conductance);
+        }
+
+        let own = self.capacity(cell);
+        let position = self.reserve(block, cell);
+
+        if layer + 1 < self.layers {
+            let conductance = self.grid.conductance_bottom(self.stack, layer + 1, row, column);
+            diagonal += self.push(block, layer + 1, row, column, -conductance);
+        }
+
+        block.values[position] = own + diagonal;
+    }
+
+    fn add_bottom_wall(&self, block: &mut Block, cell: Cell) {
+        let Cell { layer, row, column } = cell;
+        let mut diagonal = 0.0;
+
+        if layer > 0 {
+            let conductance = self.grid.conductance_top(self.stack, layer - 1, row, column);
+            diagonal += self.push(block, layer - 1, row, column, -conductance);
+        }
+
+        let own = self.capacity(cell);
+        let position = self.reserve(block, cell);
+
+        // See `add_liquid_2rm` for the difference from 3D-ICE.
+        if layer + 2 < self.layers {
+            let conductance = self.grid.conductance_top(self.stack, layer + 1, row, column);
+            diagonal += self.push(block, layer + 1, row, column, -conductance);
+            let conductance = self.grid.conductance_bottom(self.stack, layer + 2, row, column);
+            diagonal += self.push(block, layer + 2, row, column, -conductance);
+        }
+
+        block.values[position] = own + diagonal;
+    }
+
+    /// Return `C/dt` of a cell in the case of transient analysis and zero
+    /// otherwise.
+    fn capacity(&self, cell: Cell) -> f64 {
+        match self.step_time {
+            Some(step_time) => {
+                self.grid.capacity(self.stack, cell.layer, cell.row, cell.column) / step_time
+            },
+            _ => 0.0,
+        }
+    }
+
+    /// Return the contribution of the coolant entering or leaving a liquid
+    /// cell at the first or last row.
+    fn inflow(&self, cell: Cell) -> f64 {
+        if cell.row == 0 || cell.row + 1 == self.rows {
+            self.grid.conductance_north(self.stack, cell.layer, cell.row, cell.column)
+        } else {
+            0.0
+        }
+    }
+
+    /// Append an off-diagonal entry and return the negated value.
+    fn push(&self, block: &mut Block, layer: usize, row: usize, column: usize, value: f64) -> f64 {
+        block.indices.push((layer * self.rows + row) * self.columns + column);
+        block.values.push(value);
+        -value
+    }
+
+    /// Append a diagonal entry to be filled in later and return its position.
+    fn reserve(&self, block: &mut Block, cell: Cell) -> usize {
+        self.push(block, cell.layer, cell.row, cell.column, 0.0);
+        block.values.len() - 1
+    }
+}
+
+#[inline]
+fn parallel(x: f64, y: f64) -> f64 {
+    (x * y) / (x + y)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::Path;
+    use std::slice;
+    use superlu_sys as slu;
+    use temporary::Directory;
+
+    use analysis;
+    use stack;
+    use system_matrix;
+    use thermal_grid;
+    use Raw;
+
+    #[test]
+    fn assemble() {
+        let directory = Directory::new("threed_ice").unwrap();
+        let path = directory.path().join("stack.stk");
+        for name in &["channel_2rm", "channel_4rm", "default", "double", "grid", "pinfin"] {
+            let fixtures = format!("tests/fixtures/{}", name);
+            for entry in fs::read_dir(&fixtures).unwrap() {
+                let source = entry.unwrap().path();
+                if source.extension().map_or(false, |extension| extension == "stk") {
+                    write(&source, &fixtures, &path);
+                    check(&path);
+                }
+            }
+        }
+    }
+
+    fn check(path: &Path) {
+        let (stack, transient, _) = unsafe { stack::new(path).unwrap() };
+        let grid = unsafe { thermal_grid::new(&stack).unwrap() };
+        let mut steady = unsafe { analysis::new().unwrap() };
+        steady.set_steady();
+        for analysis in &[transient, steady] {
+            let expected = unsafe { system_matrix::new(&stack, analysis, &grid).unwrap() };
+            let expected = expected.raw();
+            let size = expected.Size as usize;
+            let (offsets, indices, values) = unsafe {
+                let store = &*(expected.SLUMatrix_A.Store as *const slu::NCformat);
+                let nonzeros = store.nnz as usize;
+                (slice::from_raw_parts(store.colptr, size + 1),
+                 slice::from_raw_parts(store.rowind, nonzeros),
+                 slice::from_raw_parts(store.nzval as *const f64, nonzeros))
+            };
+
+            let actual = super::assemble(&stack, analysis, &grid).unwrap();
+            assert_eq!(actual.offsets, offsets.iter().map(|&i| i as usize).collect::<Vec<_>>());
+            assert_eq!(actual.indices, indices.iter().map(|&i| i as usize).collect::<Vec<_>>());
+            for (&actual, &expected) in actual.values.iter().zip(values) {
+                let tolerance = 1e-12 * expected.abs().max(actual.abs());
+                assert!((actual - expected).abs() <= tolerance);
+            }
+        }
+    }
+
+    /// Copy a stack description referring to its floorplans relative to the
+    /// root of the package, which is the working directory of tests.
+    fn write(source: &Path, directory: &str, path: &Path) {
+        let content = fs::read_to_string(source).unwrap();
+        let content = content.replace("floorplan \"", &format!("floorplan \"{}/", directory));
+        File::create(path).unwrap().write_all(content.as_bytes()).unwrap();
+    }
+}
@@ -0,0 +1,184 @@
+use matrix::format::Compressed;
+
+use solver::{self, Preconditioner, Sparse};
+use Result;
+
+/// The preconditioned conjugate gradient method.
+pub struct ConjugateGradient {
+    matrix: Sparse,
+    preconditioner: Factor,
+    tolerance: f64,
+    iterations: usize,
+}
+
+/// A preconditioner `M` prepared for a matrix.
+enum Factor {
+    /// The inverse of the diagonal of the matrix.
+    Diagonal(Vec<f64>),
+    /// The factor `L` of `M = L L'` with the diagonal stored last in each row.
+    Lower(Sparse),
+}
+
+impl ConjugateGradient {
+    /// Prepare the method for a symmetric positive-definite matrix.
+    pub fn new(matrix: &Compressed<f64>, preconditioner: Preconditioner, tolerance: f64,
+               iterations: usize) -> Result<ConjugateGradient> {
+        if !(tolerance > 0.0) {
+            raise!("the tolerance should be positive");
+        }
+        if iterations == 0 {
+            raise!("the number of iterations should be positive");
+        }
+        let matrix = try!(Sparse::new(matrix));
+        if !matrix.is_symmetric() {
+            raise!("the conjugate gradient method requires a symmetric matrix");
+        }
+        let preconditioner = match preconditioner {
+            Preconditioner::Jacobi => try!(jacobi(&matrix)),
+            Preconditioner::IncompleteCholesky => try!(incomplete_cholesky(&matrix)),
+        };
+        Ok(ConjugateGradient {
+            matrix: matrix,
+            preconditioner: preconditioner,
+            tolerance: tolerance,
+            iterations: iterations,
+        })
+    }
+}
+
+impl ConjugateGradient {
+    /// Solve the system for a right-hand side.
+    pub fn solve(&self, b: &[f64], x: &mut [f64]) -> Result<()> {
+        let size = self.matrix.size;
+        if b.len() != size || x.len() != size {
+            raise!("the right-hand side has a wrong size");
+        }
+
+        let threshold = self.tolerance * solver::dot(b, b).sqrt();
+
+        let mut r = vec![0.0; size];
+        self.matrix.multiply(x, &mut r);
+        for i in 0..size {
+            r[i] = b[i] - r[i];
+        }
+        let mut z = r.clone();
+        self.preconditioner.apply(&mut z);
+        let mut p = z.clone();
+        let mut q = vec![0.0; size];
+        let mut rho = solver::dot(&r, &z);
+
+        for _ in 0..self.iterations {
+            if solver::dot(&r, &r).sqrt() <= threshold {
+                return Ok(());
+            }
+            self.matrix.multiply(&p, &mut q);
+            let alpha = rho / solver::dot(&p, &q);
+            for i in 0..size {
+                x[i] += alpha * p[i];
+                r[i] -= alpha * q[i];
+            }
+            z.copy_from_slice(&r);
+            self.preconditioner.apply(&mut z);
+            let next = solver::dot(&r, &z);
+            let beta = next / rho;
+            rho = next;
+            for i in 0..size {
+                p[i] = z[i] + beta * p[i];
+            }
+        }
+        if solver::dot(&r, &r).sqrt() <= threshold {
+            return Ok(());
+        }
+
+        raise!(format!("the conjugate gradient method failed to converge in {} iterations",
+                       self.iterations));
+    }
+}
+
+impl Factor {
+    /// Compute `z = M^(-1) z`.
+    fn apply(&self, z: &mut [f64]) {
+        match *self {
+            Factor::Diagonal(ref inverse) => {
+                for (value, &inverse) in z.iter_mut().zip(inverse) {
+                    *value *= inverse;
+                }
+            },
+            Factor::Lower(ref lower) => {
+                let (offsets, indices, values) = (&lower.offsets, &lower.indices, &lower.values);
+                for i in 0..lower.size {
+                    let last = offsets[i + 1] - 1;
+                    let mut sum = z[i];
+                    for k in offsets[i]..last {
+                        sum -= values[k] * z[indices[k]];
+                    }
+                    z[i] = sum / values[last];
+                }
+                for i in (0..lower.size).rev() {
+                    let last = offsets[i + 1] - 1;
+                    z[i] /= values[last];
+                    let value = z[i];
+                    for k in offsets[i]..last {
+                        z[indices[k]] -= values[k] * value;
+                    }
+                }
+            },
+        }
+    }
+}
+
+fn jacobi(matrix: &Sparse) -> Result<Factor> {
+    let mut inverse = matrix.diagonal();
+    for value in &mut inverse {
+        if !(*value > 0.0) {
+            raise!("the matrix should have a positive diagonal");
+        }
+        *value = 1.0 / *value;
+    }
+    Ok(Factor::Diagonal(inverse))
+}
+
+fn incomplete_cholesky(matrix: &Sparse) -> Result<Factor> {
+    let size = matrix.size;
+    let mut offsets = vec![0; size + 1];
+    let mut indices = vec![];
+    let mut values = vec![];
+    for i in 0..size {
+        let start = indices.len();
+        for k in matrix.offsets[i]..matrix.offsets[i + 1] {
+            let j = matrix.indices[k];
+            if j > i {
+                break;
+            }
+            let mut sum = matrix.values[k];
+            let (mut p, mut q) = (start, offsets[j]);
+            let q_end = if j == i { indices.len() } else { offsets[j + 1] - 1 };
+            while p < indices.len() && q < q_end {
+                if indices[p] < indices[q] {
+                    p += 1;
+                } else if indices[p] > indices[q] {
+                    q += 1;
+                } else {
+                    sum -= values[p] * values[q];
+                    p += 1;
+                    q += 1;
+                }
+            }
+            let value = if j == i {
+                if !(sum > 0.0) {
+                    raise!("the incomplete Cholesky factorization has broken down");
+                }
+                sum.sqrt()
+            } else {
+                sum / values[offsets[j + 1] - 1]
+            };
+            indices.push(j);
+            values.push(value);
+        }
+        if indices.last() != Some(&i) {
+            raise!("the matrix should have a positive diagonal");
+        }
+        offsets[i + 1] = indices.len();
+    }
+    Ok(Factor::Lower(Sparse { size: size, offsets: offsets, indices: indices, values: values }))
+}
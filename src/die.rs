@@ -1,6 +1,8 @@
 use ffi;
+use std::ops::Range;
 
 use floorplan::{self, Floorplan};
+use layer::{self, Layer};
 
 /// A die contained in a stack.
 #[derive(Clone, Debug, PartialEq)]
@@ -9,11 +11,36 @@ pub struct Die {
     pub id: String,
     /// The floorplan.
     pub floorplan: Floorplan,
+    /// The list of layers ordered from bottom to top.
+    pub layers: Vec<Layer>,
+    /// The position of the source layer in the list of layers.
+    pub source: usize,
+    /// The index of the bottom layer in the thermal grid.
+    pub offset: usize,
 }
 
-pub unsafe fn new(raw: &ffi::Die_t) -> Die {
+impl Die {
+    /// Return the index of the source layer in the thermal grid.
+    #[inline]
+    pub fn source_layer(&self) -> usize {
+        self.offset + self.source
+    }
+
+    /// Return the indices of the layers in the thermal grid.
+    #[inline]
+    pub fn thermal_layers(&self) -> Range<usize> {
+        self.offset..(self.offset + self.layers.len())
+    }
+}
+
+pub unsafe fn new(raw: &ffi::Die_t, offset: usize) -> Die {
+    let mut layers = layer::extract(&raw.Layers);
+    layers.reverse();
     Die {
         id: c_str_to_string!(raw.Id),
         floorplan: floorplan::new(&raw.Floorplan),
+        layers: layers,
+        source: raw.SourceLayerOffset as usize,
+        offset: offset,
     }
 }
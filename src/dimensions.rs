@@ -1,10 +1,26 @@
 use ffi;
 
 /// The dimensions of a stack.
+///
+/// The thermal nodes are indexed by layers, then by rows, and then by
+/// columns. The layers are counted from the bottom of the stack, the rows
+/// along the width of the chip, and the columns along its length. The
+/// geometric quantities are given in micrometers.
 pub struct Dimensions {
     raw: *mut ffi::Dimensions_t,
 }
 
+/// A node of a thermal grid.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Node {
+    /// The index of the layer.
+    pub layer: usize,
+    /// The index of the row.
+    pub row: usize,
+    /// The index of the column.
+    pub column: usize,
+}
+
 impl Dimensions {
     /// Return the number of columns.
     #[inline]
@@ -24,11 +40,91 @@ impl Dimensions {
         unsafe { ffi::get_number_of_layers(self.raw) as usize }
     }
 
+    /// Return the number of nodes.
+    #[inline]
+    pub fn nodes(&self) -> usize {
+        unsafe { ffi::get_number_of_cells(self.raw) as usize }
+    }
+
     /// Return the number of rows.
     #[inline]
     pub fn rows(&self) -> usize {
         unsafe { ffi::get_number_of_rows(self.raw) as usize }
     }
+
+    /// Return the length of the chip.
+    #[inline]
+    pub fn chip_length(&self) -> f64 {
+        unsafe { (*self.raw).Chip.Length }
+    }
+
+    /// Return the width of the chip.
+    #[inline]
+    pub fn chip_width(&self) -> f64 {
+        unsafe { (*self.raw).Chip.Width }
+    }
+
+    /// Return the length of the cells in a column.
+    ///
+    /// The length is the same for all columns unless the stack contains
+    /// channels modeled with four resistors.
+    pub fn cell_length(&self, column: usize) -> f64 {
+        let cell = unsafe { &(*self.raw).Cell };
+        if column == 0 {
+            cell.FirstWallLength
+        } else if column + 1 == self.columns() {
+            cell.LastWallLength
+        } else if column % 2 == 1 {
+            cell.ChannelLength
+        } else {
+            cell.WallLength
+        }
+    }
+
+    /// Return the width of the cells.
+    #[inline]
+    pub fn cell_width(&self) -> f64 {
+        unsafe { (*self.raw).Cell.Width }
+    }
+
+    /// Return the height of the cells in a layer.
+    #[inline]
+    pub fn layer_height(&self, layer: usize) -> f64 {
+        let cell = unsafe { &(*self.raw).Cell };
+        slice!(cell.Heights, cell.NHeights as usize)[layer]
+    }
+
+    /// Convert a node into its index.
+    #[inline]
+    pub fn index(&self, node: Node) -> usize {
+        (node.layer * self.rows() + node.row) * self.columns() + node.column
+    }
+
+    /// Convert an index into the corresponding node.
+    #[inline]
+    pub fn node(&self, index: usize) -> Node {
+        let (rows, columns) = (self.rows(), self.columns());
+        Node {
+            layer: index / (rows * columns),
+            row: index / columns % rows,
+            column: index % columns,
+        }
+    }
+
+    /// Return the coordinates of the center of a node.
+    pub fn center(&self, node: Node) -> (f64, f64, f64) {
+        let x = (0..node.column).map(|j| self.cell_length(j)).fold(0.0, |sum, value| sum + value);
+        let y = node.row as f64 * self.cell_width();
+        let z = (0..node.layer).map(|k| self.layer_height(k)).fold(0.0, |sum, value| sum + value);
+        let (length, width, height) = self.size(node);
+        (x + length / 2.0, y + width / 2.0, z + height / 2.0)
+    }
+
+    /// Return the length, width, and height of a node.
+    #[inline]
+    pub fn size(&self, node: Node) -> (f64, f64, f64) {
+        (self.cell_length(node.column), self.cell_width(), self.layer_height(node.layer))
+    }
 }
 
 pub fn new(raw: *mut ffi::Dimensions_t) -> Dimensions {
@@ -0,0 +1,147 @@
+use std::path::PathBuf;
+use std::{error, fmt, io};
+
+/// An error.
+#[derive(Debug)]
+pub enum Error {
+    /// A file does not exist.
+    MissingFile(PathBuf),
+    /// A path is not valid UTF-8.
+    InvalidPath(PathBuf),
+    /// A stack-description file cannot be parsed.
+    Parse {
+        /// The path to the file.
+        path: PathBuf,
+        /// The status code returned by 3D-ICE.
+        status: i32,
+        /// The problems found by the parser of the crate.
+        diagnostics: Vec<Diagnostic>,
+    },
+    /// A structure of 3D-ICE cannot be built.
+    Build {
+        /// The name of the structure.
+        object: &'static str,
+        /// The status code returned by 3D-ICE.
+        status: i32,
+    },
+    /// A routine of 3D-ICE fails.
+    Routine {
+        /// The description of the operation.
+        operation: &'static str,
+        /// The status code returned by 3D-ICE.
+        status: i32,
+    },
+    /// A description cannot be parsed by the parser of the crate.
+    Syntax(Diagnostic),
+    /// A matrix cannot be converted.
+    Conversion(&'static str),
+    /// An argument or a piece of data is invalid.
+    Invalid(String),
+    /// An input or output operation fails.
+    Io(io::Error),
+}
+
+/// A message reported by a parser.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Diagnostic {
+    /// The path to the file that the message refers to.
+    pub file: Option<PathBuf>,
+    /// The number of the line that the message refers to.
+    pub line: Option<usize>,
+    /// The number of the column that the message refers to.
+    pub column: Option<usize>,
+    /// The message, which names the offending token for syntax errors.
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref file) = self.file {
+            try!(write!(formatter, "{}:", file.display()));
+        }
+        if let Some(line) = self.line {
+            try!(write!(formatter, "{}:", line));
+            if let Some(column) = self.column {
+                try!(write!(formatter, "{}:", column));
+            }
+        }
+        if self.file.is_some() || self.line.is_some() {
+            try!(write!(formatter, " "));
+        }
+        write!(formatter, "{}", self.message)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::MissingFile(ref path) => {
+                write!(formatter, "the file {:?} does not exist", path)
+            },
+            Error::InvalidPath(ref path) => {
+                write!(formatter, "the path {:?} is not valid UTF-8", path)
+            },
+            Error::Parse { ref path, status, ref diagnostics } => {
+                try!(write!(formatter, "failed to parse the stack-description file {:?} \
+                                        (status {})", path, status));
+                for diagnostic in diagnostics {
+                    try!(write!(formatter, "\n{}", diagnostic));
+                }
+                Ok(())
+            },
+            Error::Build { object, status } => {
+                write!(formatter, "failed to build the {} (status {})", object, status)
+            },
+            Error::Routine { operation, status } => {
+                write!(formatter, "failed to {} (status {})", operation, status)
+            },
+            Error::Syntax(ref diagnostic) => write!(formatter, "{}", diagnostic),
+            Error::Conversion(object) => write!(formatter, "failed to convert the {}", object),
+            Error::Invalid(ref message) => write!(formatter, "{}", message),
+            Error::Io(ref error) => write!(formatter, "{}", error),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    #[inline]
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+impl<'l> From<&'l str> for Error {
+    #[inline]
+    fn from(message: &'l str) -> Error {
+        Error::Invalid(message.to_string())
+    }
+}
+
+impl From<String> for Error {
+    #[inline]
+    fn from(message: String) -> Error {
+        Error::Invalid(message)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> io::Error {
+        let kind = match error {
+            Error::Io(error) => return error,
+            Error::MissingFile(_) => io::ErrorKind::NotFound,
+            Error::InvalidPath(_) | Error::Invalid(_) => io::ErrorKind::InvalidInput,
+            Error::Syntax(_) => io::ErrorKind::InvalidData,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, error.to_string())
+    }
+}
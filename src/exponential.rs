@@ -0,0 +1,570 @@
+use matrix::Size;
+use matrix::format::Conventional;
+
+use analysis::AnalysisType;
+use solver::Sparse;
+use system::{self, System};
+use {Result, analysis, conductance};
+
+/// The exact solution of the transient problem.
+///
+/// The temperature is governed by `C dT/dt + G T = B P + b` where `C` is the
+/// capacitance matrix, `G` the steady-state conductance matrix, `B` the
+/// distribution matrix, and `b` the boundary vector. The solution is computed
+/// by means of the eigendecomposition `C^(-1/2) G C^(-1/2) = U Λ U'`, which
+/// requires `G` to be symmetric. Over an interval of length `t` with constant
+/// power, the modal coordinates `z = U' C^(1/2) T` evolve as
+/// `z(t) = exp(-Λ t) z(0) + Λ^(-1) (I - exp(-Λ t)) U' C^(-1/2) (B P + b)`,
+/// and the factors of this expression are cached for a fixed step time.
+///
+/// The eigendecomposition is dense, and its cost grows cubically with the
+/// number of thermal nodes.
+pub struct Exponential {
+    nodes: usize,
+    elements: usize,
+    root: Vec<f64>,
+    values: Vec<f64>,
+    vectors: Vec<f64>,
+    input: Vec<f64>,
+    offset: Vec<f64>,
+    step_time: Option<f64>,
+    decay: Vec<f64>,
+    gain: Vec<f64>,
+}
+
+impl Exponential {
+    /// Return the number of processing elements.
+    #[inline]
+    pub fn elements(&self) -> usize {
+        self.elements
+    }
+
+    /// Return the number of thermal nodes.
+    #[inline]
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// Return the eigenvalues of `C^(-1/2) G C^(-1/2)` in the ascending order.
+    #[inline]
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// Return the step time that the cached factors correspond to, which is
+    /// not set if the analysis of the system is not transient.
+    #[inline]
+    pub fn step_time(&self) -> Option<f64> {
+        self.step_time
+    }
+
+    /// Set the step time and recompute the cached factors.
+    pub fn set_step_time(&mut self, value: f64) -> Result<()> {
+        if !(value > 0.0) {
+            raise!("the step time should be positive");
+        }
+        let (decay, gain) = factors(&self.values, value);
+        self.step_time = Some(value);
+        self.decay = decay;
+        self.gain = gain;
+        Ok(())
+    }
+
+    /// Advance the temperature by an arbitrary time interval with constant
+    /// power.
+    ///
+    /// The temperature vector has one entry per thermal node and gets
+    /// overwritten. The power vector should have one entry per processing
+    /// element, and the elements should be ordered as the columns of the
+    /// distribution matrix.
+    pub fn advance(&self, temperature: &mut [f64], power: &[f64], time: f64) -> Result<()> {
+        if !(time >= 0.0) {
+            raise!("the time interval should be nonnegative");
+        }
+        let (decay, gain) = factors(&self.values, time);
+        let mut state = try!(self.project(temperature));
+        let source = try!(self.source(power));
+        update(&mut state, &source, &decay, &gain);
+        self.restore(&state, temperature);
+        Ok(())
+    }
+
+    /// Advance the temperature by one step using the cached factors.
+    ///
+    /// The arguments are the same as the ones of `advance`.
+    pub fn step(&self, temperature: &mut [f64], power: &[f64]) -> Result<()> {
+        try!(self.check_step_time());
+        let mut state = try!(self.project(temperature));
+        let source = try!(self.source(power));
+        update(&mut state, &source, &self.decay, &self.gain);
+        self.restore(&state, temperature);
+        Ok(())
+    }
+
+    /// Advance the temperature by a number of steps using the cached factors.
+    ///
+    /// The power profile is a matrix whose rows correspond to the processing
+    /// elements and columns to the time steps; the data are stored in the
+    /// column-major order. The result contains the temperature of the thermal
+    /// nodes at the end of each time step stored in the same manner, and the
+    /// temperature vector is overwritten by the last one.
+    pub fn run(&self, temperature: &mut [f64], power: &[f64]) -> Result<Vec<f64>> {
+        let (elements, nodes) = (self.elements, self.nodes);
+        if elements == 0 || power.len() % elements != 0 {
+            raise!("the power profile has a wrong size");
+        }
+        try!(self.check_step_time());
+        let mut state = try!(self.project(temperature));
+        let mut result = Vec::with_capacity(power.len() / elements * nodes);
+        for power in power.chunks(elements) {
+            let source = try!(self.source(power));
+            update(&mut state, &source, &self.decay, &self.gain);
+            self.restore(&state, temperature);
+            result.extend(temperature.iter());
+        }
+        Ok(result)
+    }
+
+    /// Return the state and input matrices of the discrete-time system that
+    /// advances the temperature by a time interval.
+    ///
+    /// The matrices `A` and `B` are such that `T(t) - T0 = A (T(0) - T0) + B P`
+    /// where `T0` is the steady-state temperature with no power dissipated.
+    pub fn discretize(&self, time: f64) -> Result<(Conventional<f64>, Conventional<f64>)> {
+        if !(time >= 0.0) {
+            raise!("the time interval should be nonnegative");
+        }
+        let (n, m) = (self.nodes, self.elements);
+        let (decay, gain) = factors(&self.values, time);
+
+        let mut a = Conventional::new((n, n));
+        let mut row = vec![0.0; n];
+        for i in 0..n {
+            for k in 0..n {
+                row[k] = self.vectors[i * n + k] * decay[k] / self.root[i];
+            }
+            for j in 0..n {
+                let column = &self.vectors[(j * n)..((j + 1) * n)];
+                let sum = row.iter().zip(column).fold(0.0, |sum, (&x, &y)| sum + x * y);
+                a.values[j * n + i] = sum * self.root[j];
+            }
+        }
+
+        let mut b = Conventional::new((n, m));
+        for j in 0..m {
+            let input = &self.input[(j * n)..((j + 1) * n)];
+            for i in 0..n {
+                let row = &self.vectors[(i * n)..((i + 1) * n)];
+                let mut sum = 0.0;
+                for k in 0..n {
+                    sum += row[k] * gain[k] * input[k];
+                }
+                b.values[j * n + i] = sum / self.root[i];
+            }
+        }
+
+        Ok((a, b))
+    }
+
+    fn check_step_time(&self) -> Result<()> {
+        if self.step_time.is_none() {
+            raise!("the step time should be set for a system with no transient analysis");
+        }
+        Ok(())
+    }
+
+    fn project(&self, temperature: &[f64]) -> Result<Vec<f64>> {
+        let n = self.nodes;
+        if temperature.len() != n {
+            raise!("the temperature vector has a wrong size");
+        }
+        let mut state = vec![0.0; n];
+        for i in 0..n {
+            let value = self.root[i] * temperature[i];
+            let row = &self.vectors[(i * n)..((i + 1) * n)];
+            for (state, &vector) in state.iter_mut().zip(row) {
+                *state += vector * value;
+            }
+        }
+        Ok(state)
+    }
+
+    fn restore(&self, state: &[f64], temperature: &mut [f64]) {
+        let n = self.nodes;
+        for i in 0..n {
+            let row = &self.vectors[(i * n)..((i + 1) * n)];
+            let sum = row.iter().zip(state).fold(0.0, |sum, (&vector, &state)| {
+                sum + vector * state
+            });
+            temperature[i] = sum / self.root[i];
+        }
+    }
+
+    fn source(&self, power: &[f64]) -> Result<Vec<f64>> {
+        let n = self.nodes;
+        if power.len() != self.elements {
+            raise!("the power vector has a wrong size");
+        }
+        let mut source = self.offset.clone();
+        for (j, &power) in power.iter().enumerate() {
+            let column = &self.input[(j * n)..((j + 1) * n)];
+            for (source, &input) in source.iter_mut().zip(column) {
+                *source += input * power;
+            }
+        }
+        Ok(source)
+    }
+}
+
+pub unsafe fn new(system: &System) -> Result<Exponential> {
+    let capacitance = try!(system.capacitance()).values;
+    let distribution = try!(system.distribution());
+    let boundary = try!(system.boundary());
+
+    let mut analysis = try!(analysis::new());
+    analysis.set_steady();
+    let conductance = {
+        let grid = try!(system::grid(system));
+        try!(Sparse::new(&try!(conductance::assemble(&system.stack, &analysis, &grid))))
+    };
+    if !conductance.is_symmetric() {
+        raise!("the exact solution requires a symmetric conductance matrix");
+    }
+
+    let (nodes, elements) = (conductance.size, distribution.columns());
+    if capacitance.iter().any(|&value| !(value > 0.0)) {
+        raise!("the capacitance should be positive");
+    }
+    let root = capacitance.iter().map(|&value| value.sqrt()).collect::<Vec<_>>();
+
+    let mut matrix = vec![0.0; nodes * nodes];
+    for i in 0..nodes {
+        for k in conductance.offsets[i]..conductance.offsets[i + 1] {
+            let j = conductance.indices[k];
+            matrix[i * nodes + j] = conductance.values[k] / (root[i] * root[j]);
+        }
+    }
+    let (values, vectors) = try!(decompose(matrix, nodes));
+
+    let mut scaled = vec![0.0; nodes * elements];
+    for (i, j, &value) in distribution.iter() {
+        scaled[j * nodes + i] += value / root[i];
+    }
+    let mut input = vec![0.0; nodes * elements];
+    for j in 0..elements {
+        transpose_multiply(&vectors, nodes, &scaled[(j * nodes)..((j + 1) * nodes)],
+                           &mut input[(j * nodes)..((j + 1) * nodes)]);
+    }
+    let scaled = boundary.iter().zip(&root).map(|(&value, &root)| value / root)
+                                           .collect::<Vec<_>>();
+    let mut offset = vec![0.0; nodes];
+    transpose_multiply(&vectors, nodes, &scaled, &mut offset);
+
+    let (step_time, decay, gain) = match system.analysis.kind() {
+        AnalysisType::Transient => {
+            let step_time = system.analysis.step_time();
+            let (decay, gain) = factors(&values, step_time);
+            (Some(step_time), decay, gain)
+        },
+        _ => (None, vec![], vec![]),
+    };
+
+    Ok(Exponential {
+        nodes: nodes,
+        elements: elements,
+        root: root,
+        values: values,
+        vectors: vectors,
+        input: input,
+        offset: offset,
+        step_time: step_time,
+        decay: decay,
+        gain: gain,
+    })
+}
+
+fn factors(values: &[f64], time: f64) -> (Vec<f64>, Vec<f64>) {
+    let decay = values.iter().map(|&value| (-value * time).exp()).collect::<Vec<_>>();
+    let gain = values.iter().zip(&decay).map(|(&value, &decay)| {
+        if value == 0.0 { time } else { (1.0 - decay) / value }
+    }).collect();
+    (decay, gain)
+}
+
+fn update(state: &mut [f64], source: &[f64], decay: &[f64], gain: &[f64]) {
+    for i in 0..state.len() {
+        state[i] = decay[i] * state[i] + gain[i] * source[i];
+    }
+}
+
+/// Compute `y = U' x` for a square matrix stored in the row-major order.
+fn transpose_multiply(matrix: &[f64], n: usize, x: &[f64], y: &mut [f64]) {
+    for value in y.iter_mut() {
+        *value = 0.0;
+    }
+    for i in 0..n {
+        let row = &matrix[(i * n)..((i + 1) * n)];
+        for (y, &entry) in y.iter_mut().zip(row) {
+            *y += entry * x[i];
+        }
+    }
+}
+
+/// Compute the eigendecomposition of a symmetric matrix stored in the
+/// row-major order.
+///
+/// The eigenvalues are returned in the ascending order, and the eigenvectors
+/// are the columns of the second matrix. The matrix is reduced to the
+/// tridiagonal form by Householder transformations, and the latter is
+/// diagonalized by the implicit QL method.
+fn decompose(mut v: Vec<f64>, n: usize) -> Result<(Vec<f64>, Vec<f64>)> {
+    let mut d = vec![0.0; n];
+    let mut e = vec![0.0; n];
+    tridiagonalize(&mut v, &mut d, &mut e, n);
+    try!(diagonalize(&mut v, &mut d, &mut e, n));
+    if d.iter().any(|value| value.is_nan()) {
+        raise!("the eigendecomposition has produced invalid values");
+    }
+
+    let mut order = (0..n).collect::<Vec<_>>();
+    order.sort_by(|&i, &j| d[i].partial_cmp(&d[j]).unwrap());
+    let values = order.iter().map(|&i| d[i]).collect();
+    let mut vectors = vec![0.0; n * n];
+    for i in 0..n {
+        for (k, &j) in order.iter().enumerate() {
+            vectors[i * n + k] = v[i * n + j];
+        }
+    }
+    Ok((values, vectors))
+}
+
+fn tridiagonalize(v: &mut [f64], d: &mut [f64], e: &mut [f64], n: usize) {
+    for j in 0..n {
+        d[j] = v[(n - 1) * n + j];
+    }
+    for i in (1..n).rev() {
+        let mut scale = 0.0;
+        let mut h = 0.0;
+        for k in 0..i {
+            scale += d[k].abs();
+        }
+        if scale == 0.0 {
+            e[i] = d[i - 1];
+            for j in 0..i {
+                d[j] = v[(i - 1) * n + j];
+                v[i * n + j] = 0.0;
+                v[j * n + i] = 0.0;
+            }
+        } else {
+            for k in 0..i {
+                d[k] /= scale;
+                h += d[k] * d[k];
+            }
+            let mut f = d[i - 1];
+            let mut g = h.sqrt();
+            if f > 0.0 {
+                g = -g;
+            }
+            e[i] = scale * g;
+            h -= f * g;
+            d[i - 1] = f - g;
+            for j in 0..i {
+                e[j] = 0.0;
+            }
+            for j in 0..i {
+                f = d[j];
+                v[j * n + i] = f;
+                g = e[j] + v[j * n + j] * f;
+                for k in (j + 1)..i {
+                    g += v[k * n + j] * d[k];
+                    e[k] += v[k * n + j] * f;
+                }
+                e[j] = g;
+            }
+            f = 0.0;
+            for j in 0..i {
+                e[j] /= h;
+                f += e[j] * d[j];
+            }
+            let hh = f / (h + h);
+            for j in 0..i {
+                e[j] -= hh * d[j];
+            }
+            for j in 0..i {
+                f = d[j];
+                g = e[j];
+                for k in j..i {
+                    v[k * n + j] -= f * e[k] + g * d[k];
+                }
+                d[j] = v[(i - 1) * n + j];
+                v[i * n + j] = 0.0;
+            }
+        }
+        d[i] = h;
+    }
+
+    for i in 0..(n - 1) {
+        v[(n - 1) * n + i] = v[i * n + i];
+        v[i * n + i] = 1.0;
+        let h = d[i + 1];
+        if h != 0.0 {
+            for k in 0..(i + 1) {
+                d[k] = v[k * n + i + 1] / h;
+            }
+            for j in 0..(i + 1) {
+                let mut g = 0.0;
+                for k in 0..(i + 1) {
+                    g += v[k * n + i + 1] * v[k * n + j];
+                }
+                for k in 0..(i + 1) {
+                    v[k * n + j] -= g * d[k];
+                }
+            }
+        }
+        for k in 0..(i + 1) {
+            v[k * n + i + 1] = 0.0;
+        }
+    }
+    for j in 0..n {
+        d[j] = v[(n - 1) * n + j];
+        v[(n - 1) * n + j] = 0.0;
+    }
+    v[(n - 1) * n + n - 1] = 1.0;
+    e[0] = 0.0;
+}
+
+fn diagonalize(v: &mut [f64], d: &mut [f64], e: &mut [f64], n: usize) -> Result<()> {
+    use std::f64::EPSILON;
+
+    for i in 1..n {
+        e[i - 1] = e[i];
+    }
+    e[n - 1] = 0.0;
+
+    let mut f = 0.0;
+    let mut tst1 = 0.0f64;
+    for l in 0..n {
+        tst1 = tst1.max(d[l].abs() + e[l].abs());
+        let mut m = l;
+        while m < n - 1 && e[m].abs() > EPSILON * tst1 {
+            m += 1;
+        }
+        if m > l {
+            let mut iterations = 0;
+            loop {
+                iterations += 1;
+                if iterations > 30 {
+                    raise!("the eigendecomposition failed to converge");
+                }
+                let mut g = d[l];
+                let mut p = (d[l + 1] - g) / (2.0 * e[l]);
+                let mut r = p.hypot(1.0);
+                if p < 0.0 {
+                    r = -r;
+                }
+                d[l] = e[l] / (p + r);
+                d[l + 1] = e[l] * (p + r);
+                let dl1 = d[l + 1];
+                let mut h = g - d[l];
+                for i in (l + 2)..n {
+                    d[i] -= h;
+                }
+                f += h;
+
+                p = d[m];
+                let (mut c, mut c2, mut c3) = (1.0, 1.0, 1.0);
+                let el1 = e[l + 1];
+                let (mut s, mut s2) = (0.0, 0.0);
+                for i in (l..m).rev() {
+                    c3 = c2;
+                    c2 = c;
+                    s2 = s;
+                    g = c * e[i];
+                    h = c * p;
+                    r = p.hypot(e[i]);
+                    e[i + 1] = s * r;
+                    s = e[i] / r;
+                    c = p / r;
+                    p = c * d[i] - s * g;
+                    d[i + 1] = h + s * (c * g + s * d[i]);
+                    for k in 0..n {
+                        h = v[k * n + i + 1];
+                        v[k * n + i + 1] = s * v[k * n + i] + c * h;
+                        v[k * n + i] = c * v[k * n + i] - s * h;
+                    }
+                }
+                p = -s * s2 * c3 * el1 * e[l] / dl1;
+                e[l] = s * p;
+                d[l] = c * p;
+                if e[l].abs() <= EPSILON * tst1 {
+                    break;
+                }
+            }
+        }
+        d[l] += f;
+        e[l] = 0.0;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decompose;
+
+    #[test]
+    fn decompose_dense() {
+        let n = 7;
+        let mut a = vec![0.0; n * n];
+        for i in 0..n {
+            for j in 0..n {
+                let sign = if (i + j) % 2 == 0 { 1.0 } else { -1.0 };
+                a[i * n + j] = sign / (i + j + 1) as f64;
+            }
+            a[i * n + i] += i as f64 - 3.0;
+        }
+        check(&a, n);
+    }
+
+    #[test]
+    fn decompose_repeated() {
+        check(&[1.0; 4 * 4], 4);
+        check(&[2.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 2.0], 3);
+        check(&[5.0], 1);
+    }
+
+    #[test]
+    fn decompose_tridiagonal() {
+        let n = 5;
+        let mut a = vec![0.0; n * n];
+        for i in 0..n {
+            a[i * n + i] = 2.0;
+            if i + 1 < n {
+                a[i * n + i + 1] = -1.0;
+                a[(i + 1) * n + i] = -1.0;
+            }
+        }
+        check(&a, n);
+    }
+
+    /// Check that `U' A U = Λ` and `U' U = I`, and that the eigenvalues are
+    /// in the ascending order.
+    fn check(a: &[f64], n: usize) {
+        let (values, vectors) = decompose(a.to_vec(), n).unwrap();
+        assert!(values.windows(2).all(|pair| pair[0] <= pair[1]));
+        let scale = a.iter().fold(1.0f64, |scale, &value| scale.max(value.abs()));
+        for i in 0..n {
+            for j in 0..n {
+                let (mut product, mut identity) = (0.0, 0.0);
+                for k in 0..n {
+                    identity += vectors[k * n + i] * vectors[k * n + j];
+                    for l in 0..n {
+                        product += vectors[k * n + i] * a[k * n + l] * vectors[l * n + j];
+                    }
+                }
+                let expected = if i == j { values[i] } else { 0.0 };
+                assert!((product - expected).abs() < 1e-12 * scale);
+                assert!((identity - if i == j { 1.0 } else { 0.0 }).abs() < 1e-12);
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use matrix::format::Compressed;
+use std::mem;
+use std::os::raw::c_int;
+use superlu_sys as slu;
+
+use {Error, Result};
+
+/// An LU factorization of a sparse square matrix.
+///
+/// The factors are computed once by SuperLU and can then be used to solve for
+/// any number of right-hand sides, either one at a time or in batches.
+pub struct Factorization {
+    size: usize,
+    lower: slu::SuperMatrix,
+    upper: slu::SuperMatrix,
+    row_permutation: Vec<c_int>,
+    column_permutation: Vec<c_int>,
+}
+
+impl Factorization {
+    /// Factorize a matrix.
+    pub fn new(matrix: &Compressed<f64>) -> Result<Factorization> {
+        unsafe { new(matrix) }
+    }
+
+    /// Return the number of rows and columns of the factorized matrix.
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Solve the system for a right-hand side, which gets overwritten by the
+    /// solution.
+    #[inline]
+    pub fn solve(&self, b: &mut [f64]) -> Result<()> {
+        if b.len() != self.size {
+            raise!("the right-hand side has a wrong size");
+        }
+        unsafe { solve(self, b, 1) }
+    }
+
+    /// Solve the system for several right-hand sides, which get overwritten by
+    /// the solutions.
+    ///
+    /// The right-hand sides are the columns of a matrix with one row per
+    /// unknown stored in the column-major order.
+    #[inline]
+    pub fn solve_many(&self, b: &mut [f64]) -> Result<()> {
+        if self.size == 0 || b.len() % self.size != 0 {
+            raise!("the right-hand sides have a wrong size");
+        }
+        let columns = b.len() / self.size;
+        if columns == 0 {
+            return Ok(());
+        }
+        unsafe { solve(self, b, columns) }
+    }
+}
+
+impl Drop for Factorization {
+    fn drop(&mut self) {
+        unsafe {
+            slu::Destroy_SuperNode_Matrix(&mut self.lower);
+            slu::Destroy_CompCol_Matrix(&mut self.upper);
+        }
+    }
+}
+
+// The factors allocated by SuperLU are owned exclusively by the factorization.
+unsafe impl Send for Factorization {}
+
+unsafe fn new(matrix: &Compressed<f64>) -> Result<Factorization> {
+    use matrix::Size;
+
+    let size = matrix.rows();
+    if size == 0 || size != matrix.columns() {
+        raise!("the matrix should be square and nonempty");
+    }
+
+    let mut offsets = vec![0 as c_int; size + 1];
+    for (_, j, _) in matrix.iter() {
+        offsets[j + 1] += 1;
+    }
+    for j in 0..size {
+        offsets[j + 1] += offsets[j];
+    }
+    let nonzeros = offsets[size] as usize;
+    let mut values = vec![0.0; nonzeros];
+    let mut indices = vec![0 as c_int; nonzeros];
+    let mut next = offsets.clone();
+    for (i, j, &value) in matrix.iter() {
+        let k = next[j] as usize;
+        values[k] = value;
+        indices[k] = i as c_int;
+        next[j] += 1;
+    }
+
+    let mut a = mem::zeroed();
+    slu::dCreate_CompCol_Matrix(&mut a, size as c_int, size as c_int, nonzeros as c_int,
+                                values.as_mut_ptr(), indices.as_mut_ptr(), offsets.as_mut_ptr(),
+                                slu::Stype_t::SLU_NC, slu::Dtype_t::SLU_D, slu::Mtype_t::SLU_GE);
+
+    let mut empty = vec![0.0; size];
+    let mut b = mem::zeroed();
+    slu::dCreate_Dense_Matrix(&mut b, size as c_int, 0, empty.as_mut_ptr(), size as c_int,
+                              slu::Stype_t::SLU_DN, slu::Dtype_t::SLU_D, slu::Mtype_t::SLU_GE);
+
+    let mut options = mem::zeroed();
+    slu::set_default_options(&mut options);
+    let mut stat = mem::zeroed();
+    slu::StatInit(&mut stat);
+
+    let mut row_permutation = vec![0; size];
+    let mut column_permutation = vec![0; size];
+    let (mut lower, mut upper) = (mem::zeroed(), mem::zeroed());
+    let mut info = 0;
+    slu::dgssv(&mut options, &mut a, column_permutation.as_mut_ptr(),
+               row_permutation.as_mut_ptr(), &mut lower, &mut upper, &mut b, &mut stat,
+               &mut info);
+
+    slu::StatFree(&mut stat);
+    slu::Destroy_SuperMatrix_Store(&mut b);
+    slu::Destroy_SuperMatrix_Store(&mut a);
+
+    if info != 0 {
+        if info as usize <= size {
+            slu::Destroy_SuperNode_Matrix(&mut lower);
+            slu::Destroy_CompCol_Matrix(&mut upper);
+        }
+        raise!(Error::Routine { operation: "factorize the matrix", status: info as i32 });
+    }
+
+    Ok(Factorization {
+        size: size,
+        lower: lower,
+        upper: upper,
+        row_permutation: row_permutation,
+        column_permutation: column_permutation,
+    })
+}
+
+unsafe fn solve(factorization: &Factorization, b: &mut [f64], columns: usize) -> Result<()> {
+    let size = factorization.size;
+
+    let mut matrix = mem::zeroed();
+    slu::dCreate_Dense_Matrix(&mut matrix, size as c_int, columns as c_int, b.as_mut_ptr(),
+                              size as c_int, slu::Stype_t::SLU_DN, slu::Dtype_t::SLU_D,
+                              slu::Mtype_t::SLU_GE);
+
+    let mut stat = mem::zeroed();
+    slu::StatInit(&mut stat);
+    let mut info = 0;
+    dgstrs(slu::trans_t::NOTRANS, &factorization.lower as *const _ as *mut _,
+           &factorization.upper as *const _ as *mut _,
+           factorization.column_permutation.as_ptr() as *mut _,
+           factorization.row_permutation.as_ptr() as *mut _, &mut matrix, &mut stat, &mut info);
+    slu::StatFree(&mut stat);
+    slu::Destroy_SuperMatrix_Store(&mut matrix);
+
+    if info != 0 {
+        raise!(Error::Routine { operation: "solve the system", status: info as i32 });
+    }
+    Ok(())
+}
+
+// The routine is a part of SuperLU but is not exposed by superlu-sys.
+extern "C" {
+    fn dgstrs(trans: slu::trans_t, l: *mut slu::SuperMatrix, u: *mut slu::SuperMatrix,
+              perm_c: *mut c_int, perm_r: *mut c_int, b: *mut slu::SuperMatrix,
+              stat: *mut slu::SuperLUStat_t, info: *mut c_int);
+}
@@ -1,4 +1,9 @@
 use ffi;
+use std::io::Write;
+use std::ops::Range;
+
+use identifier;
+use Result;
 
 /// The floorplan of the source layer of a die.
 #[derive(Clone, Debug, PartialEq)]
@@ -14,6 +19,124 @@ pub struct FloorplanElement {
     pub id: String,
     /// The area.
     pub area: f64,
+    /// The power values.
+    pub power: Vec<f64>,
+    /// The rectangles constituting the element.
+    pub rectangles: Vec<Rectangle>,
+}
+
+/// A rectangle of a floorplan element.
+///
+/// The coordinates and sizes are given in micrometers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rectangle {
+    /// The abscissa of the south-west corner.
+    pub x: f64,
+    /// The ordinate of the south-west corner.
+    pub y: f64,
+    /// The length along the abscissa.
+    pub length: f64,
+    /// The width along the ordinate.
+    pub width: f64,
+    /// The rows of the thermal grid covered by the rectangle.
+    pub rows: Range<usize>,
+    /// The columns of the thermal grid covered by the rectangle.
+    pub columns: Range<usize>,
+}
+
+impl Floorplan {
+    /// Write in the format of floorplan files.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for (i, element) in self.elements.iter().enumerate() {
+            if i > 0 {
+                try!(writeln!(writer));
+            }
+            try!(writeln!(writer, "{}:", try!(identifier::check(&element.id))));
+            match element.rectangles.len() {
+                0 => raise!("a floorplan element should have at least one rectangle"),
+                1 => {
+                    let rectangle = &element.rectangles[0];
+                    try!(write!(writer, "   position {:?}, {:?};\n   dimension {:?}, {:?};\n",
+                                rectangle.x, rectangle.y, rectangle.length, rectangle.width));
+                },
+                _ => for rectangle in &element.rectangles {
+                    try!(writeln!(writer, "   rectangle ({:?}, {:?}, {:?}, {:?});",
+                                  rectangle.x, rectangle.y, rectangle.length, rectangle.width));
+                },
+            }
+            if !element.power.is_empty() {
+                let power = element.power.iter().map(|value| format!("{:?}", value))
+                                                .collect::<Vec<_>>();
+                try!(writeln!(writer, "   power values {};", power.join(", ")));
+            }
+        }
+        Ok(try!(writer.flush()))
+    }
+}
+
+impl FloorplanElement {
+    /// Create an element given its rectangles and power values.
+    pub fn new(id: &str, rectangles: Vec<Rectangle>, power: Vec<f64>) -> FloorplanElement {
+        let area = rectangles.iter().fold(0.0, |sum, rectangle| {
+            sum + rectangle.length * rectangle.width
+        });
+        FloorplanElement {
+            id: id.to_string(),
+            area: area,
+            power: power,
+            rectangles: rectangles,
+        }
+    }
+
+    /// Return the smallest rectangle containing all the rectangles of the
+    /// element.
+    pub fn bounding_box(&self) -> Option<Rectangle> {
+        let mut rectangles = self.rectangles.iter();
+        let mut result = match rectangles.next() {
+            Some(rectangle) => rectangle.clone(),
+            _ => return None,
+        };
+        for rectangle in rectangles {
+            let (x, y) = (result.x.min(rectangle.x), result.y.min(rectangle.y));
+            result.length = (result.x + result.length).max(rectangle.x + rectangle.length) - x;
+            result.width = (result.y + result.width).max(rectangle.y + rectangle.width) - y;
+            result.x = x;
+            result.y = y;
+            result.rows = result.rows.start.min(rectangle.rows.start)..
+                          result.rows.end.max(rectangle.rows.end);
+            result.columns = result.columns.start.min(rectangle.columns.start)..
+                             result.columns.end.max(rectangle.columns.end);
+        }
+        Some(result)
+    }
+
+    /// Return the cells of the thermal grid covered by the element as pairs
+    /// of row and column indices.
+    pub fn cells(&self) -> Vec<(usize, usize)> {
+        self.rectangles.iter().flat_map(|rectangle| rectangle.cells()).collect()
+    }
+}
+
+impl Rectangle {
+    /// Create a rectangle given its south-west corner and sizes.
+    ///
+    /// The covered rows and columns are unknown until the rectangle is placed
+    /// in a stack, and they are left empty.
+    pub fn new(x: f64, y: f64, length: f64, width: f64) -> Rectangle {
+        Rectangle { x: x, y: y, length: length, width: width, rows: 0..0, columns: 0..0 }
+    }
+
+    /// Return the cells of the thermal grid covered by the rectangle as pairs
+    /// of row and column indices.
+    pub fn cells(&self) -> Vec<(usize, usize)> {
+        let mut cells = Vec::with_capacity(self.rows.len() * self.columns.len());
+        for i in self.rows.clone() {
+            for j in self.columns.clone() {
+                cells.push((i, j));
+            }
+        }
+        cells
+    }
 }
 
 pub unsafe fn new(raw: &ffi::Floorplan_t) -> Floorplan {
@@ -28,5 +151,39 @@ pub unsafe fn new(raw: &ffi::Floorplan_t) -> Floorplan {
 }
 
 unsafe fn new_element(raw: &ffi::FloorplanElement_t) -> FloorplanElement {
-    FloorplanElement { id: c_str_to_string!(raw.Id), area: raw.Area }
+    FloorplanElement {
+        id: c_str_to_string!(raw.Id),
+        area: raw.Area,
+        power: extract_power(raw.PowerValues),
+        rectangles: extract_rectangles(&raw.ICElements),
+    }
+}
+
+unsafe fn extract_rectangles(raw: &ffi::ICElementList_t) -> Vec<Rectangle> {
+    let mut rectangles = vec![];
+    let mut cursor = raw.First;
+    for _ in 0..raw.Size {
+        assert!(!cursor.is_null());
+        let element = &(*cursor).Data;
+        rectangles.push(Rectangle {
+            x: element.SW_X,
+            y: element.SW_Y,
+            length: element.Length,
+            width: element.Width,
+            rows: (element.SW_Row as usize)..(element.NE_Row as usize + 1),
+            columns: (element.SW_Column as usize)..(element.NE_Column as usize + 1),
+        });
+        cursor = (*cursor).Next;
+    }
+    rectangles
+}
+
+unsafe fn extract_power(raw: *const ffi::PowersQueue_t) -> Vec<f64> {
+    if raw.is_null() || (*raw).Memory.is_null() {
+        return vec![];
+    }
+    let raw = &*raw;
+    let (capacity, start) = (raw.Capacity as usize, raw.Start as usize);
+    let memory = slice!(raw.Memory, capacity);
+    (0..(raw.Size as usize)).map(|i| memory[(start + i) % capacity]).collect()
 }
@@ -0,0 +1,72 @@
+use ffi;
+
+use material::{self, Material};
+
+/// A heat sink.
+///
+/// The heat sink is placed on top of the stack.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeatSink {
+    /// The model.
+    pub model: HeatSinkModel,
+    /// The heat transfer coefficient between the top surface and the ambient.
+    pub heat_transfer_coefficient: f64,
+    /// The ambient temperature.
+    pub ambient_temperature: f64,
+    /// The sink, which is present only in the traditional model.
+    pub sink: Option<HeatSinkPart>,
+    /// The spreader, which is present only in the traditional model.
+    pub spreader: Option<HeatSinkPart>,
+}
+
+/// A model of a heat sink.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HeatSinkModel {
+    /// A direct connection of the top-most layer to the ambient.
+    ConnectionToAmbient,
+    /// A spreader and a sink placed on top of the top-most layer.
+    Traditional,
+    /// An undefined model.
+    None,
+}
+
+/// A part of a heat sink.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeatSinkPart {
+    /// The height.
+    pub height: f64,
+    /// The area.
+    pub area: f64,
+    /// The material.
+    pub material: Material,
+}
+
+pub unsafe fn new(raw: &ffi::HeatSink_t) -> HeatSink {
+    let model = match raw.SinkModel {
+        ffi::TDICE_HEATSINK_MODEL_CONNECTION_TO_AMBIENT => HeatSinkModel::ConnectionToAmbient,
+        ffi::TDICE_HEATSINK_MODEL_TRADITIONAL => HeatSinkModel::Traditional,
+        ffi::TDICE_HEATSINK_MODEL_NONE => HeatSinkModel::None,
+    };
+    let (sink, spreader) = match model {
+        HeatSinkModel::Traditional => (
+            Some(HeatSinkPart {
+                height: raw.SinkHeight,
+                area: raw.SinkArea,
+                material: material::new(&raw.SinkMaterial),
+            }),
+            Some(HeatSinkPart {
+                height: raw.SpreaderHeight,
+                area: raw.SpreaderArea,
+                material: material::new(&raw.SpreaderMaterial),
+            }),
+        ),
+        _ => (None, None),
+    };
+    HeatSink {
+        model: model,
+        heat_transfer_coefficient: raw.AmbientHTC,
+        ambient_temperature: raw.AmbientTemperature,
+        sink: sink,
+        spreader: spreader,
+    }
+}
@@ -0,0 +1,21 @@
+use Result;
+
+/// Check that a string is a valid identifier in the stack-description and
+/// floorplan formats.
+pub fn check(id: &str) -> Result<&str> {
+    let mut chars = id.chars();
+    if !chars.next().map_or(false, |c| c.is_ascii_alphabetic()) ||
+       !chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+
+        raise!("an identifier should consist of letters, digits, and underscores");
+    }
+    Ok(id)
+}
+
+/// Check that a string is a valid path in the stack-description format.
+///
+/// The rule is the one of the lexer of 3D-ICE as patched by `threed-ice-sys`:
+/// a path is any nonempty sequence of characters other than double quotes.
+pub fn is_path(path: &str) -> bool {
+    !path.is_empty() && !path.contains('"')
+}
@@ -0,0 +1,35 @@
+use ffi;
+
+use material::{self, Material};
+
+/// A layer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+    /// The identifier, which is absent for the layers of dies.
+    pub id: Option<String>,
+    /// The height.
+    pub height: f64,
+    /// The material.
+    pub material: Material,
+}
+
+pub unsafe fn new(raw: &ffi::Layer_t) -> Layer {
+    Layer {
+        id: if raw.Id.is_null() { None } else { Some(c_str_to_string!(raw.Id)) },
+        height: raw.Height,
+        material: material::new(&raw.Material),
+    }
+}
+
+pub unsafe fn extract(raw: &ffi::LayerList_t) -> Vec<Layer> {
+    let mut layers = vec![];
+
+    let mut cursor = raw.First;
+    for _ in 0..raw.Size {
+        assert!(!cursor.is_null());
+        layers.push(new(&(*cursor).Data));
+        cursor = (*cursor).Next;
+    }
+
+    layers
+}
@@ -1,32 +1,26 @@
 extern crate matrix;
 extern crate superlu;
+extern crate superlu_sys;
+extern crate temporary;
 extern crate threed_ice_sys as ffi;
 
 macro_rules! raise(
-    ($message:expr) => (raise!(Other, $message));
-    ($kind:ident, $message:expr) => (
-        return Err(::std::io::Error::new(::std::io::ErrorKind::$kind, $message))
-    );
-);
-
-macro_rules! ok(
-    ($result:expr) => (match $result {
-        Ok(ok) => ok,
-        _ => raise!("something went wrong"),
-    });
+    ($error:expr) => (return Err(::std::convert::From::from($error)));
 );
 
 macro_rules! some(
-    ($result:expr) => (match $result {
+    ($option:expr, $error:expr) => (match $option {
         Some(some) => some,
-        _ => raise!("something went wrong"),
+        _ => raise!($error),
     });
 );
 
 macro_rules! success(
-    ($result:expr, $message:expr) => (if $result != ::ffi::TDICE_SUCCESS {
-        raise!(concat!("failed to ", $message));
+    ($result:expr, $kind:ident, $field:ident: $value:expr) => (match $result {
+        ::ffi::TDICE_SUCCESS => {},
+        status => raise!(::Error::$kind { $field: $value, status: status as i32 }),
     });
+    ($result:expr, $operation:expr) => (success!($result, Routine, operation: $operation));
 );
 
 macro_rules! c_str_to_string(
@@ -37,11 +31,16 @@ macro_rules! c_str_to_string(
 );
 
 macro_rules! str_to_cstr(
-    ($str:expr) => (ok!(::std::ffi::CString::new($str)));
+    ($str:expr) => (match ::std::ffi::CString::new($str) {
+        Ok(string) => string,
+        _ => raise!("a string should not contain null characters"),
+    });
 );
 
 macro_rules! path_to_cstr(
-    ($path:expr) => (str_to_cstr!(some!($path.to_str())));
+    ($path:expr) => (
+        str_to_cstr!(some!($path.to_str(), ::Error::InvalidPath($path.to_path_buf())))
+    );
 );
 
 macro_rules! slice(
@@ -50,9 +49,6 @@ macro_rules! slice(
     });
 );
 
-/// An error.
-pub type Error = std::io::Error;
-
 /// A result.
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -97,19 +93,47 @@ macro_rules! implement_raw(
 );
 
 mod analysis;
+mod builder;
+mod channel;
+mod conductance;
+mod conjugate_gradient;
 mod die;
 mod dimensions;
+mod error;
+mod exponential;
+mod factorization;
 mod floorplan;
+mod heat_sink;
+mod identifier;
+mod layer;
+mod material;
+mod multigrid;
 mod output;
+pub mod parser;
 mod power_grid;
+mod simulator;
+mod solver;
 mod stack;
+mod state_space;
 mod system;
 mod system_matrix;
 mod thermal_grid;
 
 pub use analysis::{Analysis, AnalysisType};
+pub use builder::StackBuilder;
+pub use channel::{Channel, ChannelModel, Coolant};
 pub use die::Die;
-pub use dimensions::Dimensions;
-pub use floorplan::{Floorplan, FloorplanElement};
+pub use dimensions::{Dimensions, Node};
+pub use error::{Diagnostic, Error};
+pub use exponential::Exponential;
+pub use factorization::Factorization;
+pub use floorplan::{Floorplan, FloorplanElement, Rectangle};
+pub use heat_sink::{HeatSink, HeatSinkModel, HeatSinkPart};
+pub use layer::Layer;
+pub use material::Material;
+pub use output::{Output, OutputInstant, OutputInstruction, OutputQuantity, OutputType};
+pub use simulator::Simulator;
+pub use solver::{Preconditioner, Solver};
 pub use stack::{Stack, StackElement};
+pub use state_space::{Discretization, StateSpace};
 pub use system::System;
@@ -0,0 +1,20 @@
+use ffi;
+
+/// A material.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    /// The identifier.
+    pub id: String,
+    /// The thermal conductivity.
+    pub thermal_conductivity: f64,
+    /// The volumetric heat capacity.
+    pub volumetric_heat_capacity: f64,
+}
+
+pub unsafe fn new(raw: &ffi::Material_t) -> Material {
+    Material {
+        id: c_str_to_string!(raw.Id),
+        thermal_conductivity: raw.ThermalConductivity,
+        volumetric_heat_capacity: raw.VolumetricHeatCapacity,
+    }
+}
@@ -0,0 +1,426 @@
+use matrix::format::Compressed;
+
+use factorization::Factorization;
+use solver::{self, Sparse};
+use Result;
+
+/// The number of block Gauss-Seidel sweeps performed before and after the
+/// correction on each level.
+const SWEEPS: usize = 2;
+
+/// The geometric multigrid method.
+///
+/// The levels are obtained by coarsening the rows and columns of the thermal
+/// grid by a factor of two within each layer, and the layers themselves are
+/// kept intact. The transfer between levels is done by bilinear interpolation,
+/// the coarse matrices are the Galerkin products, and the coarsest system is
+/// solved directly. Since the coupling between layers is typically much
+/// stronger than the one within layers, the smoother relaxes all the cells
+/// of a vertical column at once.
+pub struct Multigrid {
+    size: usize,
+    levels: Vec<Level>,
+    coarsest: Factorization,
+    tolerance: f64,
+    iterations: usize,
+}
+
+struct Level {
+    matrix: Sparse,
+    interpolation: Vec<Vec<(usize, f64)>>,
+    coarse: usize,
+    columns: Columns,
+}
+
+/// The LU factorizations of the blocks of a matrix that couple the cells of
+/// each vertical column.
+struct Columns {
+    layers: usize,
+    plane: usize,
+    factors: Vec<f64>,
+    pivots: Vec<usize>,
+}
+
+impl Multigrid {
+    /// Prepare the method for a matrix of a grid with the given numbers of
+    /// layers, rows, and columns.
+    pub fn new(matrix: &Compressed<f64>, (layers, rows, columns): (usize, usize, usize),
+               tolerance: f64, iterations: usize) -> Result<Multigrid> {
+        if !(tolerance > 0.0) {
+            raise!("the tolerance should be positive");
+        }
+        if iterations == 0 {
+            raise!("the number of iterations should be positive");
+        }
+        let mut matrix = try!(Sparse::new(matrix));
+        let size = matrix.size;
+        if matrix.size != layers * rows * columns {
+            raise!("the matrix does not correspond to the grid");
+        }
+        if matrix.diagonal().iter().any(|&value| value == 0.0) {
+            raise!("the matrix should have a nonzero diagonal");
+        }
+
+        let (mut rows, mut columns) = (rows, columns);
+        let mut levels = vec![];
+        while rows > 2 || columns > 2 {
+            let (coarse_rows, row_weights) = interpolate(rows);
+            let (coarse_columns, column_weights) = interpolate(columns);
+            let coarse = layers * coarse_rows * coarse_columns;
+            let mut interpolation = Vec::with_capacity(matrix.size);
+            for layer in 0..layers {
+                for row in 0..rows {
+                    for column in 0..columns {
+                        let mut weights = vec![];
+                        for &(i, u) in &row_weights[row] {
+                            for &(j, v) in &column_weights[column] {
+                                let k = (layer * coarse_rows + i) * coarse_columns + j;
+                                weights.push((k, u * v));
+                            }
+                        }
+                        interpolation.push(weights);
+                    }
+                }
+            }
+            let next = galerkin(&matrix, &interpolation, coarse);
+            let blocks = try!(Columns::new(&matrix, layers, rows * columns));
+            levels.push(Level {
+                matrix: matrix,
+                interpolation: interpolation,
+                coarse: coarse,
+                columns: blocks,
+            });
+            matrix = next;
+            rows = coarse_rows;
+            columns = coarse_columns;
+        }
+        let coarsest = try!(Factorization::new(&matrix.compressed()));
+
+        Ok(Multigrid {
+            size: size,
+            levels: levels,
+            coarsest: coarsest,
+            tolerance: tolerance,
+            iterations: iterations,
+        })
+    }
+
+    /// Return the number of levels above the coarsest one.
+    #[cfg(test)]
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Solve the system for a right-hand side.
+    pub fn solve(&self, b: &[f64], x: &mut [f64]) -> Result<()> {
+        let size = self.size;
+        if b.len() != size || x.len() != size {
+            raise!("the right-hand side has a wrong size");
+        }
+        if self.levels.is_empty() {
+            x.copy_from_slice(b);
+            return self.coarsest.solve(x);
+        }
+
+        let matrix = &self.levels[0].matrix;
+        let threshold = self.tolerance * solver::dot(b, b).sqrt();
+        let mut r = vec![0.0; size];
+        for _ in 0..self.iterations {
+            residual(matrix, b, x, &mut r);
+            if solver::dot(&r, &r).sqrt() <= threshold {
+                return Ok(());
+            }
+            try!(self.cycle(0, b, x));
+        }
+        residual(matrix, b, x, &mut r);
+        if solver::dot(&r, &r).sqrt() <= threshold {
+            return Ok(());
+        }
+
+        raise!(format!("the multigrid method failed to converge in {} iterations",
+                       self.iterations));
+    }
+
+    fn cycle(&self, index: usize, b: &[f64], x: &mut [f64]) -> Result<()> {
+        if index == self.levels.len() {
+            x.copy_from_slice(b);
+            return self.coarsest.solve(x);
+        }
+        let level = &self.levels[index];
+        for _ in 0..SWEEPS {
+            level.smooth(b, x, false);
+        }
+
+        let mut r = vec![0.0; level.matrix.size];
+        residual(&level.matrix, b, x, &mut r);
+        let mut coarse_b = vec![0.0; level.coarse];
+        for (weights, &value) in level.interpolation.iter().zip(&r) {
+            for &(k, weight) in weights {
+                coarse_b[k] += weight * value;
+            }
+        }
+        let mut coarse_x = vec![0.0; level.coarse];
+        try!(self.cycle(index + 1, &coarse_b, &mut coarse_x));
+        for (weights, value) in level.interpolation.iter().zip(x.iter_mut()) {
+            for &(k, weight) in weights {
+                *value += weight * coarse_x[k];
+            }
+        }
+
+        for _ in 0..SWEEPS {
+            level.smooth(b, x, true);
+        }
+        Ok(())
+    }
+}
+
+/// Compute the weights of the linear interpolation from a coarsened
+/// dimension of a cell-centered grid.
+fn interpolate(fine: usize) -> (usize, Vec<Vec<(usize, f64)>>) {
+    if fine <= 2 {
+        return (fine, (0..fine).map(|i| vec![(i, 1.0)]).collect());
+    }
+    let coarse = (fine + 1) / 2;
+    let weights = (0..fine).map(|i| {
+        let k = i / 2;
+        let neighbor = if i % 2 == 0 { k.checked_sub(1) } else { Some(k + 1) };
+        match neighbor {
+            Some(neighbor) if neighbor < coarse && 2 * k + 1 < fine => {
+                vec![(k, 0.75), (neighbor, 0.25)]
+            },
+            _ => vec![(k, 1.0)],
+        }
+    }).collect();
+    (coarse, weights)
+}
+
+/// Compute `P' A P` where `P` is the interpolation.
+fn galerkin(matrix: &Sparse, interpolation: &[Vec<(usize, f64)>], coarse: usize) -> Sparse {
+    let mut restriction = vec![vec![]; coarse];
+    for (i, weights) in interpolation.iter().enumerate() {
+        for &(k, weight) in weights {
+            restriction[k].push((i, weight));
+        }
+    }
+
+    let mut offsets = Vec::with_capacity(coarse + 1);
+    let mut indices = vec![];
+    let mut values = vec![];
+    let mut marker = vec![usize::max_value(); coarse];
+    offsets.push(0);
+    for (k, weights) in restriction.iter().enumerate() {
+        let start = indices.len();
+        for &(i, u) in weights {
+            for p in matrix.offsets[i]..matrix.offsets[i + 1] {
+                let value = u * matrix.values[p];
+                for &(l, v) in &interpolation[matrix.indices[p]] {
+                    if marker[l] == k {
+                        let position = start + indices[start..].iter().position(|&other| {
+                            other == l
+                        }).unwrap();
+                        values[position] += value * v;
+                    } else {
+                        marker[l] = k;
+                        indices.push(l);
+                        values.push(value * v);
+                    }
+                }
+            }
+        }
+        let mut row = indices[start..].iter().cloned().zip(values[start..].iter().cloned())
+                                      .collect::<Vec<_>>();
+        row.sort_by(|&(i, _), &(j, _)| i.cmp(&j));
+        for (p, (index, value)) in row.into_iter().enumerate() {
+            indices[start + p] = index;
+            values[start + p] = value;
+        }
+        offsets.push(indices.len());
+    }
+
+    Sparse { size: coarse, offsets: offsets, indices: indices, values: values }
+}
+
+/// Compute `r = b - A x`.
+fn residual(matrix: &Sparse, b: &[f64], x: &[f64], r: &mut [f64]) {
+    matrix.multiply(x, r);
+    for (r, &b) in r.iter_mut().zip(b) {
+        *r = b - *r;
+    }
+}
+
+impl Level {
+    /// Perform a block Gauss-Seidel sweep over the vertical columns in the
+    /// forward or backward order.
+    fn smooth(&self, b: &[f64], x: &mut [f64], backward: bool) {
+        let (matrix, layers, plane) = (&self.matrix, self.columns.layers, self.columns.plane);
+        let mut rhs = vec![0.0; layers];
+        let mut relax = |position: usize, x: &mut [f64]| {
+            for layer in 0..layers {
+                let i = layer * plane + position;
+                let mut sum = b[i];
+                for k in matrix.offsets[i]..matrix.offsets[i + 1] {
+                    let j = matrix.indices[k];
+                    if j % plane != position {
+                        sum -= matrix.values[k] * x[j];
+                    }
+                }
+                rhs[layer] = sum;
+            }
+            self.columns.solve(position, &mut rhs);
+            for layer in 0..layers {
+                x[layer * plane + position] = rhs[layer];
+            }
+        };
+        if backward {
+            for position in (0..plane).rev() {
+                relax(position, x);
+            }
+        } else {
+            for position in 0..plane {
+                relax(position, x);
+            }
+        }
+    }
+}
+
+impl Columns {
+    fn new(matrix: &Sparse, layers: usize, plane: usize) -> Result<Columns> {
+        let mut factors = vec![0.0; plane * layers * layers];
+        let mut pivots = vec![0; plane * layers];
+        for position in 0..plane {
+            let a = &mut factors[(position * layers * layers)..((position + 1) * layers * layers)];
+            let pivots = &mut pivots[(position * layers)..((position + 1) * layers)];
+            for i in 0..layers {
+                for j in 0..layers {
+                    a[i * layers + j] = matrix.get(i * plane + position, j * plane + position);
+                }
+            }
+            for k in 0..layers {
+                let mut p = k;
+                for i in (k + 1)..layers {
+                    if a[i * layers + k].abs() > a[p * layers + k].abs() {
+                        p = i;
+                    }
+                }
+                if a[p * layers + k] == 0.0 {
+                    raise!("the matrix has a singular block");
+                }
+                pivots[k] = p;
+                if p != k {
+                    for j in 0..layers {
+                        a.swap(k * layers + j, p * layers + j);
+                    }
+                }
+                for i in (k + 1)..layers {
+                    a[i * layers + k] /= a[k * layers + k];
+                    let factor = a[i * layers + k];
+                    for j in (k + 1)..layers {
+                        a[i * layers + j] -= factor * a[k * layers + j];
+                    }
+                }
+            }
+        }
+        Ok(Columns { layers: layers, plane: plane, factors: factors, pivots: pivots })
+    }
+
+    /// Solve the system of the cells of a vertical column in place.
+    fn solve(&self, position: usize, x: &mut [f64]) {
+        let layers = self.layers;
+        let a = &self.factors[(position * layers * layers)..((position + 1) * layers * layers)];
+        let pivots = &self.pivots[(position * layers)..((position + 1) * layers)];
+        for k in 0..layers {
+            x.swap(k, pivots[k]);
+        }
+        for i in 0..layers {
+            for j in 0..i {
+                x[i] -= a[i * layers + j] * x[j];
+            }
+        }
+        for i in (0..layers).rev() {
+            for j in (i + 1)..layers {
+                x[i] -= a[i * layers + j] * x[j];
+            }
+            x[i] /= a[i * layers + i];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix::format::Compressed;
+
+    use solver::Sparse;
+    use super::Multigrid;
+
+    #[test]
+    fn levels() {
+        for &(grid, levels) in &[((1, 2, 2), 0), ((1, 3, 2), 1), ((2, 16, 16), 3),
+                                  ((3, 16, 5), 3), ((1, 17, 1), 4)] {
+            let multigrid = Multigrid::new(&laplacian(grid), grid, 1e-12, 100).unwrap();
+            assert_eq!(multigrid.levels(), levels);
+        }
+    }
+
+    #[test]
+    fn solve() {
+        let grid = (3, 16, 12);
+        let matrix = laplacian(grid);
+        let size = grid.0 * grid.1 * grid.2;
+        let expected = (0..size).map(|i| 1.0 + (i % 7) as f64).collect::<Vec<_>>();
+        let mut b = vec![0.0; size];
+        for (i, j, &value) in matrix.iter() {
+            b[i] += value * expected[j];
+        }
+
+        let multigrid = Multigrid::new(&matrix, grid, 1e-14, 100).unwrap();
+        let mut x = vec![0.0; size];
+        multigrid.solve(&b, &mut x).unwrap();
+        for (&x, &y) in x.iter().zip(&expected) {
+            assert!((x - y).abs() < 1e-6);
+        }
+    }
+
+    /// Construct the matrix of a grid of cells connected to their neighbors,
+    /// with a weak coupling to the ground and a strong one between layers.
+    fn laplacian((layers, rows, columns): (usize, usize, usize)) -> Compressed<f64> {
+        let index = |layer: usize, row: usize, column: usize| {
+            (layer * rows + row) * columns + column
+        };
+        let size = layers * rows * columns;
+        let (mut offsets, mut indices, mut values) = (vec![0], vec![], vec![]);
+        for layer in 0..layers {
+            for row in 0..rows {
+                for column in 0..columns {
+                    let mut entries = vec![(index(layer, row, column), 0.01)];
+                    let mut connect = |j: usize, weight: f64| {
+                        entries[0].1 += weight;
+                        entries.push((j, -weight));
+                    };
+                    if layer > 0 {
+                        connect(index(layer - 1, row, column), 100.0);
+                    }
+                    if layer + 1 < layers {
+                        connect(index(layer + 1, row, column), 100.0);
+                    }
+                    if row > 0 {
+                        connect(index(layer, row - 1, column), 1.0);
+                    }
+                    if row + 1 < rows {
+                        connect(index(layer, row + 1, column), 1.0);
+                    }
+                    if column > 0 {
+                        connect(index(layer, row, column - 1), 1.0);
+                    }
+                    if column + 1 < columns {
+                        connect(index(layer, row, column + 1), 1.0);
+                    }
+                    entries.sort_by(|&(i, _), &(j, _)| i.cmp(&j));
+                    indices.extend(entries.iter().map(|&(j, _)| j));
+                    values.extend(entries.iter().map(|&(_, value)| value));
+                    offsets.push(indices.len());
+                }
+            }
+        }
+        Sparse { size: size, offsets: offsets, indices: indices, values: values }.compressed()
+    }
+}
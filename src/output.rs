@@ -3,10 +3,79 @@ use std::mem;
 
 use Result;
 
+/// The output of a temperature analysis.
 pub struct Output {
+    /// The list of instructions.
+    pub instructions: Vec<OutputInstruction>,
+
     raw: ffi::Output_t,
 }
 
+/// An instruction of an output.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputInstruction {
+    /// The type.
+    pub kind: OutputType,
+    /// The identifier of the stack element.
+    pub element: String,
+    /// The name of the file.
+    pub file: String,
+    /// The quantity.
+    pub quantity: OutputQuantity,
+    /// The instant.
+    pub instant: OutputInstant,
+}
+
+/// A type of output.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OutputType {
+    /// The temperature of the cell containing a point.
+    Tcell {
+        /// The abscissa of the point.
+        x: f64,
+        /// The ordinate of the point.
+        y: f64,
+    },
+    /// The temperature of the elements of a floorplan.
+    Tflp,
+    /// The temperature of an element of a floorplan.
+    Tflpel(String),
+    /// The thermal map of a stack element.
+    Tmap,
+    /// The power map of a stack element.
+    Pmap,
+    /// The temperature of the coolant leaving a channel.
+    Tcoolant,
+    /// An undefined output.
+    None,
+}
+
+/// A quantity of output.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputQuantity {
+    /// The average.
+    Average,
+    /// The maximum.
+    Maximum,
+    /// The minimum.
+    Minimum,
+    /// An undefined quantity.
+    None,
+}
+
+/// An instant of output.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputInstant {
+    /// At the end of the simulation.
+    Final,
+    /// At the end of each time slot.
+    Slot,
+    /// At the end of each time step.
+    Step,
+    /// An undefined instant.
+    None,
+}
+
 impl Drop for Output {
     fn drop(&mut self) {
         unsafe { ffi::output_destroy(&mut self.raw) };
@@ -15,8 +84,56 @@ impl Drop for Output {
 
 implement_raw!(Output, ffi::Output_t);
 
+// The list of instructions allocated by 3D-ICE is owned exclusively by the
+// output.
+unsafe impl Send for Output {}
+
 pub unsafe fn new() -> Result<Output> {
     let mut raw = mem::uninitialized();
     ffi::output_init(&mut raw);
-    Ok(Output { raw: raw })
+    Ok(Output { instructions: vec![], raw: raw })
+}
+
+pub unsafe fn extract(output: &mut Output) {
+    let mut instructions = vec![];
+    for list in &[&output.raw.InspectionPointListFinal, &output.raw.InspectionPointListSlot,
+                  &output.raw.InspectionPointListStep] {
+        let mut cursor = list.First;
+        for _ in 0..list.Size {
+            assert!(!cursor.is_null());
+            instructions.push(new_instruction(&(*cursor).Data));
+            cursor = (*cursor).Next;
+        }
+    }
+    output.instructions = instructions;
+}
+
+unsafe fn new_instruction(raw: &ffi::InspectionPoint_t) -> OutputInstruction {
+    OutputInstruction {
+        kind: match raw.Type {
+            ffi::TDICE_OUTPUT_TYPE_TCELL => OutputType::Tcell { x: raw.Xval, y: raw.Yval },
+            ffi::TDICE_OUTPUT_TYPE_TFLP => OutputType::Tflp,
+            ffi::TDICE_OUTPUT_TYPE_TFLPEL => {
+                OutputType::Tflpel(c_str_to_string!((*raw.FloorplanElement).Id))
+            },
+            ffi::TDICE_OUTPUT_TYPE_TMAP => OutputType::Tmap,
+            ffi::TDICE_OUTPUT_TYPE_PMAP => OutputType::Pmap,
+            ffi::TDICE_OUTPUT_TYPE_TCOOLANT => OutputType::Tcoolant,
+            ffi::TDICE_OUTPUT_TYPE_NONE => OutputType::None,
+        },
+        element: c_str_to_string!((*raw.StackElement).Id),
+        file: c_str_to_string!(raw.FileName),
+        quantity: match raw.Quantity {
+            ffi::TDICE_OUTPUT_QUANTITY_AVERAGE => OutputQuantity::Average,
+            ffi::TDICE_OUTPUT_QUANTITY_MAXIMUM => OutputQuantity::Maximum,
+            ffi::TDICE_OUTPUT_QUANTITY_MINIMUM => OutputQuantity::Minimum,
+            ffi::TDICE_OUTPUT_QUANTITY_NONE => OutputQuantity::None,
+        },
+        instant: match raw.Instant {
+            ffi::TDICE_OUTPUT_INSTANT_FINAL => OutputInstant::Final,
+            ffi::TDICE_OUTPUT_INSTANT_SLOT => OutputInstant::Slot,
+            ffi::TDICE_OUTPUT_INSTANT_STEP => OutputInstant::Step,
+            ffi::TDICE_OUTPUT_INSTANT_NONE => OutputInstant::None,
+        },
+    }
 }
@@ -0,0 +1,121 @@
+use std::collections::HashSet;
+
+use super::Spanned;
+use super::lexer::{self, Lexicon, Stream};
+use Result;
+
+/// A floorplan.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Floorplan {
+    /// The list of elements.
+    pub elements: Vec<Spanned<FloorplanElement>>,
+}
+
+/// An element of a floorplan.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloorplanElement {
+    /// The identifier.
+    pub id: Spanned<String>,
+    /// The list of rectangles.
+    pub rectangles: Vec<Spanned<Rectangle>>,
+    /// The list of power values.
+    pub power: Vec<Spanned<f64>>,
+}
+
+/// A rectangle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rectangle {
+    /// The abscissa of the south-west corner.
+    pub x: Spanned<f64>,
+    /// The ordinate of the south-west corner.
+    pub y: Spanned<f64>,
+    /// The length.
+    pub length: Spanned<f64>,
+    /// The width.
+    pub width: Spanned<f64>,
+}
+
+const LEXICON: Lexicon = Lexicon {
+    keywords: &["dimension", "position", "power", "rectangle", "values"],
+    symbols: "(),:;",
+    paths: false,
+};
+
+pub fn parse(input: &str) -> Result<Floorplan> {
+    let mut stream = try!(Stream::new(input, &LEXICON));
+    let mut elements = vec![try!(parse_element(&mut stream))];
+    while !stream.is_end() {
+        elements.push(try!(parse_element(&mut stream)));
+    }
+
+    let mut ids = HashSet::new();
+    for element in &elements {
+        let id = &element.id;
+        if !ids.insert(&id.node) {
+            raise!(lexer::fail(id.span, format!("the floorplan element `{}` is already declared",
+                                                id.node)));
+        }
+    }
+
+    Ok(Floorplan { elements: elements })
+}
+
+fn parse_element(stream: &mut Stream) -> Result<Spanned<FloorplanElement>> {
+    let start = stream.span();
+    let id = try!(stream.identifier());
+    try!(stream.symbol(':'));
+
+    let mut rectangles = vec![];
+    if stream.is_keyword("position") {
+        let start = try!(stream.keyword("position"));
+        let x = try!(stream.number());
+        try!(stream.symbol(','));
+        let y = try!(stream.number());
+        try!(stream.symbol(';'));
+        try!(stream.keyword("dimension"));
+        let length = try!(stream.number());
+        try!(stream.symbol(','));
+        let width = try!(stream.number());
+        try!(stream.symbol(';'));
+        rectangles.push(stream.spanned(start, Rectangle {
+            x: x,
+            y: y,
+            length: length,
+            width: width,
+        }));
+    } else if stream.is_keyword("rectangle") {
+        while stream.is_keyword("rectangle") {
+            rectangles.push(try!(parse_rectangle(stream)));
+        }
+    } else {
+        return stream.expected("keyword `position` or `rectangle`");
+    }
+
+    let mut power = vec![];
+    if stream.is_keyword("power") {
+        try!(stream.keywords(&["power", "values"]));
+        power.push(try!(stream.number()));
+        while stream.is_symbol(',') {
+            try!(stream.symbol(','));
+            power.push(try!(stream.number()));
+        }
+        try!(stream.symbol(';'));
+    }
+
+    Ok(stream.spanned(start, FloorplanElement { id: id, rectangles: rectangles, power: power }))
+}
+
+fn parse_rectangle(stream: &mut Stream) -> Result<Spanned<Rectangle>> {
+    let start = try!(stream.keyword("rectangle"));
+    try!(stream.symbol('('));
+    let x = try!(stream.number());
+    try!(stream.symbol(','));
+    let y = try!(stream.number());
+    try!(stream.symbol(','));
+    let length = try!(stream.number());
+    try!(stream.symbol(','));
+    let width = try!(stream.number());
+    try!(stream.symbol(')'));
+    try!(stream.symbol(';'));
+    Ok(stream.spanned(start, Rectangle { x: x, y: y, length: length, width: width }))
+}
@@ -0,0 +1,354 @@
+use std::{cmp, fmt};
+
+use super::{Span, Spanned};
+use identifier;
+use {Diagnostic, Error, Result};
+
+/// A token.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Identifier(String),
+    Keyword(&'static str),
+    Number(f64),
+    Path(String),
+    Symbol(char),
+    End,
+}
+
+/// The tokens recognized by a grammar.
+pub struct Lexicon {
+    pub keywords: &'static [&'static str],
+    pub symbols: &'static str,
+    pub paths: bool,
+}
+
+/// A stream of tokens.
+pub struct Stream {
+    tokens: Vec<(Token, Span)>,
+    position: usize,
+}
+
+struct Cursor<'l> {
+    input: &'l str,
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Token::Identifier(ref name) => write!(formatter, "identifier `{}`", name),
+            Token::Keyword(name) => write!(formatter, "keyword `{}`", name),
+            Token::Number(_) => write!(formatter, "number"),
+            Token::Path(ref path) => write!(formatter, "path {:?}", path),
+            Token::Symbol(symbol) => write!(formatter, "`{}`", symbol),
+            Token::End => write!(formatter, "end of input"),
+        }
+    }
+}
+
+impl Stream {
+    /// Split the input into tokens.
+    pub fn new(input: &str, lexicon: &Lexicon) -> Result<Stream> {
+        let mut cursor = Cursor { input: input, offset: 0, line: 1, column: 1 };
+        let mut tokens = vec![];
+        loop {
+            try!(cursor.skip());
+            let start = cursor.span();
+            if let Some(keyword) = cursor.numeric_keyword(lexicon) {
+                for _ in keyword.chars() {
+                    cursor.advance();
+                }
+                tokens.push((Token::Keyword(keyword), cursor.since(start)));
+                continue;
+            }
+            let token = match cursor.peek(0) {
+                None => {
+                    tokens.push((Token::End, start));
+                    break;
+                },
+                Some(c) if c.is_ascii_alphabetic() => {
+                    let name = cursor.take(|c| c == '_' || c.is_ascii_alphanumeric());
+                    match lexicon.keywords.iter().find(|&&keyword| keyword == name) {
+                        Some(&keyword) => Token::Keyword(keyword),
+                        _ => Token::Identifier(name.to_string()),
+                    }
+                },
+                Some(_) if cursor.is_number() => try!(cursor.number()),
+                Some('"') if lexicon.paths => try!(cursor.path()),
+                Some(c) if lexicon.symbols.contains(c) => {
+                    cursor.advance();
+                    Token::Symbol(c)
+                },
+                Some(c) => raise!(fail(start, format!("unexpected character {:?}", c))),
+            };
+            tokens.push((token, cursor.since(start)));
+        }
+        Ok(Stream { tokens: tokens, position: 0 })
+    }
+
+    /// Return the span of the current token.
+    #[inline]
+    pub fn span(&self) -> Span {
+        self.tokens[self.position].1
+    }
+
+    /// Check if the current token is a keyword.
+    #[inline]
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        match self.tokens[self.position].0 {
+            Token::Keyword(name) => name == keyword,
+            _ => false,
+        }
+    }
+
+    /// Check if the current token is a symbol.
+    #[inline]
+    pub fn is_symbol(&self, symbol: char) -> bool {
+        self.tokens[self.position].0 == Token::Symbol(symbol)
+    }
+
+    /// Check if the input is exhausted.
+    #[inline]
+    pub fn is_end(&self) -> bool {
+        self.tokens[self.position].0 == Token::End
+    }
+
+    /// Consume a keyword.
+    pub fn keyword(&mut self, keyword: &'static str) -> Result<Span> {
+        if !self.is_keyword(keyword) {
+            return self.expected(&format!("keyword `{}`", keyword));
+        }
+        Ok(self.next())
+    }
+
+    /// Consume a sequence of keywords.
+    pub fn keywords(&mut self, keywords: &[&'static str]) -> Result<Span> {
+        let start = self.span();
+        for &keyword in keywords {
+            try!(self.keyword(keyword));
+        }
+        Ok(self.since(start))
+    }
+
+    /// Consume one keyword out of several.
+    pub fn choose(&mut self, keywords: &[&'static str]) -> Result<Spanned<&'static str>> {
+        for &keyword in keywords {
+            if self.is_keyword(keyword) {
+                return Ok(Spanned { node: keyword, span: self.next() });
+            }
+        }
+        self.expected(&list(keywords))
+    }
+
+    /// Consume a symbol.
+    pub fn symbol(&mut self, symbol: char) -> Result<Span> {
+        if !self.is_symbol(symbol) {
+            return self.expected(&format!("`{}`", symbol));
+        }
+        Ok(self.next())
+    }
+
+    /// Consume an identifier.
+    pub fn identifier(&mut self) -> Result<Spanned<String>> {
+        let name = match self.tokens[self.position].0 {
+            Token::Identifier(ref name) => name.clone(),
+            _ => return self.expected("identifier"),
+        };
+        Ok(Spanned { node: name, span: self.next() })
+    }
+
+    /// Consume a number.
+    pub fn number(&mut self) -> Result<Spanned<f64>> {
+        let value = match self.tokens[self.position].0 {
+            Token::Number(value) => value,
+            _ => return self.expected("number"),
+        };
+        Ok(Spanned { node: value, span: self.next() })
+    }
+
+    /// Consume a path.
+    pub fn path(&mut self) -> Result<Spanned<String>> {
+        let path = match self.tokens[self.position].0 {
+            Token::Path(ref path) => path.clone(),
+            _ => return self.expected("path"),
+        };
+        Ok(Spanned { node: path, span: self.next() })
+    }
+
+    /// Consume a sequence of keywords followed by a number and a symbol.
+    pub fn field(&mut self, keywords: &[&'static str], terminator: char)
+                 -> Result<Spanned<f64>> {
+
+        try!(self.keywords(keywords));
+        let value = try!(self.number());
+        try!(self.symbol(terminator));
+        Ok(value)
+    }
+
+    /// Return the span from the beginning of a span to the end of the last
+    /// consumed token.
+    pub fn since(&self, start: Span) -> Span {
+        let end = self.tokens[..self.position].last().map_or(start.end, |&(_, span)| span.end);
+        Span { end: cmp::max(start.end, end), ..start }
+    }
+
+    /// Wrap a node into the span from the beginning of a span to the end of the
+    /// last consumed token.
+    #[inline]
+    pub fn spanned<T>(&self, start: Span, node: T) -> Spanned<T> {
+        Spanned { node: node, span: self.since(start) }
+    }
+
+    /// Report the current token as unexpected.
+    pub fn expected<T>(&self, what: &str) -> Result<T> {
+        let (ref token, span) = self.tokens[self.position];
+        raise!(fail(span, format!("expected {}, found {}", what, token)))
+    }
+
+    fn next(&mut self) -> Span {
+        let span = self.tokens[self.position].1;
+        if self.position + 1 < self.tokens.len() {
+            self.position += 1;
+        }
+        span
+    }
+}
+
+impl<'l> Cursor<'l> {
+    fn peek(&self, ahead: usize) -> Option<char> {
+        self.input[self.offset..].chars().nth(ahead)
+    }
+
+    fn advance(&mut self) {
+        if let Some(c) = self.peek(0) {
+            self.offset += c.len_utf8();
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+    }
+
+    fn take<F>(&mut self, accept: F) -> &'l str where F: Fn(char) -> bool {
+        let start = self.offset;
+        while self.peek(0).map_or(false, |c| accept(c)) {
+            self.advance();
+        }
+        &self.input[start..self.offset]
+    }
+
+    fn span(&self) -> Span {
+        Span { start: self.offset, end: self.offset, line: self.line, column: self.column }
+    }
+
+    fn since(&self, start: Span) -> Span {
+        Span { end: self.offset, ..start }
+    }
+
+    fn skip(&mut self) -> Result<()> {
+        loop {
+            match (self.peek(0), self.peek(1)) {
+                (Some(c), _) if c.is_whitespace() => self.advance(),
+                (Some('/'), Some('/')) => {
+                    self.take(|c| c != '\n');
+                },
+                (Some('/'), Some('*')) => {
+                    let start = self.span();
+                    self.advance();
+                    self.advance();
+                    loop {
+                        match (self.peek(0), self.peek(1)) {
+                            (Some('*'), Some('/')) => break,
+                            (None, _) => raise!(fail(start, "unterminated comment".to_string())),
+                            _ => self.advance(),
+                        }
+                    }
+                    self.advance();
+                    self.advance();
+                },
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn numeric_keyword(&self, lexicon: &Lexicon) -> Option<&'static str> {
+        let rest = &self.input[self.offset..];
+        lexicon.keywords.iter().find(|keyword| {
+            keyword.starts_with(|c: char| c.is_ascii_digit()) && rest.starts_with(*keyword)
+        }).cloned()
+    }
+
+    fn is_number(&self) -> bool {
+        match (self.peek(0), self.peek(1)) {
+            (Some(c), _) if c.is_ascii_digit() => true,
+            (Some('+'), Some(c)) | (Some('-'), Some(c)) => c.is_ascii_digit(),
+            _ => false,
+        }
+    }
+
+    fn number(&mut self) -> Result<Token> {
+        let start = self.span();
+        if let Some('+') | Some('-') = self.peek(0) {
+            self.advance();
+        }
+        self.take(|c| c.is_ascii_digit());
+        if self.peek(0) == Some('.') && self.peek(1).map_or(false, |c| c.is_ascii_digit()) {
+            self.advance();
+            self.take(|c| c.is_ascii_digit());
+        }
+        if let Some('e') | Some('E') = self.peek(0) {
+            let digit = match (self.peek(1), self.peek(2)) {
+                (Some('+'), Some(c)) | (Some('-'), Some(c)) => c.is_ascii_digit(),
+                (Some(c), _) => c.is_ascii_digit(),
+                _ => false,
+            };
+            if digit {
+                self.advance();
+                self.advance();
+                self.take(|c| c.is_ascii_digit());
+            }
+        }
+        let text = &self.input[start.start..self.offset];
+        match text.parse() {
+            Ok(value) => Ok(Token::Number(value)),
+            _ => raise!(fail(self.since(start), format!("invalid number {:?}", text))),
+        }
+    }
+
+    fn path(&mut self) -> Result<Token> {
+        let start = self.span();
+        self.advance();
+        let path = self.take(|c| c != '"');
+        if self.peek(0) != Some('"') {
+            raise!(fail(start, "unterminated path".to_string()));
+        }
+        self.advance();
+        if !identifier::is_path(path) {
+            raise!(fail(self.since(start), format!("invalid path {:?}", path)));
+        }
+        Ok(Token::Path(path.to_string()))
+    }
+}
+
+/// Create an error pointing at a span.
+pub fn fail(span: Span, message: String) -> Error {
+    Error::Syntax(Diagnostic {
+        file: None,
+        line: Some(span.line),
+        column: Some(span.column),
+        message: message,
+    })
+}
+
+fn list(keywords: &[&'static str]) -> String {
+    let names = keywords.iter().map(|keyword| format!("`{}`", keyword)).collect::<Vec<_>>();
+    match names.len() {
+        0 => String::new(),
+        1 => format!("keyword {}", names[0]),
+        n => format!("keyword {} or {}", names[..(n - 1)].join(", "), names[n - 1]),
+    }
+}
@@ -0,0 +1,89 @@
+//! Parsers of stack-description and floorplan files.
+//!
+//! The parsers are written in Rust and are independent of 3D-ICE. They produce
+//! syntax trees in which every node carries its location in the source, and
+//! they report problems with the line and column where they occur, which makes
+//! them suitable for checking files before passing them to 3D-ICE.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::ops::Deref;
+use std::path::Path;
+
+use {Error, Result};
+
+mod floorplan;
+mod lexer;
+mod stack;
+
+pub use self::floorplan::{Floorplan, FloorplanElement, Rectangle};
+pub use self::stack::{Channel, Coolant, Die, Dimensions, HeatSink, HeatSinkPart, Layer, Material};
+pub use self::stack::{OutputInstruction, OutputType, Solver, Stack, StackElement};
+
+/// A region of the source.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Span {
+    /// The byte offset of the beginning.
+    pub start: usize,
+    /// The byte offset of the end, which is exclusive.
+    pub end: usize,
+    /// The line of the beginning starting from one.
+    pub line: usize,
+    /// The column of the beginning starting from one.
+    pub column: usize,
+}
+
+/// A node of a syntax tree together with its region of the source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spanned<T> {
+    /// The node.
+    pub node: T,
+    /// The region.
+    pub span: Span,
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.node
+    }
+}
+
+/// Parse a stack description.
+#[inline]
+pub fn parse_stack(input: &str) -> Result<Stack> {
+    stack::parse(input)
+}
+
+/// Parse a floorplan.
+#[inline]
+pub fn parse_floorplan(input: &str) -> Result<Floorplan> {
+    floorplan::parse(input)
+}
+
+/// Read and parse a stack-description file.
+pub fn read_stack<T: AsRef<Path>>(path: T) -> Result<Stack> {
+    read(path.as_ref(), parse_stack)
+}
+
+/// Read and parse a floorplan file.
+pub fn read_floorplan<T: AsRef<Path>>(path: T) -> Result<Floorplan> {
+    read(path.as_ref(), parse_floorplan)
+}
+
+fn read<F, T>(path: &Path, parse: F) -> Result<T> where F: Fn(&str) -> Result<T> {
+    if fs::metadata(path).is_err() {
+        raise!(Error::MissingFile(path.to_path_buf()));
+    }
+    let mut input = String::new();
+    try!(try!(File::open(path)).read_to_string(&mut input));
+    match parse(&input) {
+        Err(Error::Syntax(mut diagnostic)) => {
+            diagnostic.file = Some(path.to_path_buf());
+            raise!(Error::Syntax(diagnostic));
+        },
+        result => result,
+    }
+}
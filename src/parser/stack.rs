@@ -0,0 +1,787 @@
+use std::collections::HashSet;
+
+use analysis::AnalysisType;
+use channel::ChannelModel;
+use heat_sink::HeatSinkModel;
+use output::{OutputInstant, OutputQuantity};
+use super::lexer::{self, Lexicon, Stream};
+use super::Spanned;
+use Result;
+
+/// A stack description.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stack {
+    /// The list of materials.
+    pub materials: Vec<Spanned<Material>>,
+    /// The heat sink.
+    pub heat_sink: Option<Spanned<HeatSink>>,
+    /// The channel.
+    pub channel: Option<Spanned<Channel>>,
+    /// The list of layers declared outside dies.
+    pub layers: Vec<Spanned<Layer>>,
+    /// The list of dies.
+    pub dies: Vec<Spanned<Die>>,
+    /// The dimensions.
+    pub dimensions: Spanned<Dimensions>,
+    /// The list of elements ordered from top to bottom.
+    pub elements: Vec<Spanned<StackElement>>,
+    /// The solver.
+    pub solver: Spanned<Solver>,
+    /// The list of output instructions.
+    pub output: Vec<Spanned<OutputInstruction>>,
+}
+
+/// A material.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    /// The identifier.
+    pub id: Spanned<String>,
+    /// The thermal conductivity.
+    pub thermal_conductivity: Spanned<f64>,
+    /// The volumetric heat capacity.
+    pub volumetric_heat_capacity: Spanned<f64>,
+}
+
+/// A heat sink.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeatSink {
+    /// The model.
+    pub model: HeatSinkModel,
+    /// The heat transfer coefficient.
+    pub heat_transfer_coefficient: Spanned<f64>,
+    /// The ambient temperature.
+    pub ambient_temperature: Spanned<f64>,
+    /// The sink.
+    pub sink: Option<Spanned<HeatSinkPart>>,
+    /// The spreader.
+    pub spreader: Option<Spanned<HeatSinkPart>>,
+}
+
+/// A part of a heat sink.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeatSinkPart {
+    /// The height.
+    pub height: Spanned<f64>,
+    /// The area.
+    pub area: Spanned<f64>,
+    /// The identifier of the material.
+    pub material: Spanned<String>,
+}
+
+/// A channel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Channel {
+    /// The model.
+    pub model: Spanned<ChannelModel>,
+    /// The height.
+    pub height: Spanned<f64>,
+    /// The length of the channels in the case of microchannels.
+    pub channel_length: Option<Spanned<f64>>,
+    /// The length of the walls in the case of microchannels.
+    pub wall_length: Option<Spanned<f64>>,
+    /// The length of the first wall in the case of microchannels.
+    pub first_wall_length: Option<Spanned<f64>>,
+    /// The length of the last wall in the case of microchannels.
+    pub last_wall_length: Option<Spanned<f64>>,
+    /// The diameter of the pins in the case of pin fins.
+    pub pin_diameter: Option<Spanned<f64>>,
+    /// The pitch of the pins in the case of pin fins.
+    pub pin_pitch: Option<Spanned<f64>>,
+    /// The identifier of the material of the walls or pins.
+    pub wall_material: Spanned<String>,
+    /// The coolant.
+    pub coolant: Coolant,
+}
+
+/// A coolant.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Coolant {
+    /// The flow rate in milliliters per minute in the case of microchannels.
+    pub flow_rate: Option<Spanned<f64>>,
+    /// The Darcy velocity in the case of pin fins.
+    pub darcy_velocity: Option<Spanned<f64>>,
+    /// The heat transfer coefficient on the sides.
+    pub side_heat_transfer_coefficient: Option<Spanned<f64>>,
+    /// The heat transfer coefficient on the top.
+    pub top_heat_transfer_coefficient: Option<Spanned<f64>>,
+    /// The heat transfer coefficient on the bottom.
+    pub bottom_heat_transfer_coefficient: Option<Spanned<f64>>,
+    /// The volumetric heat capacity.
+    pub volumetric_heat_capacity: Spanned<f64>,
+    /// The incoming temperature.
+    pub temperature: Spanned<f64>,
+}
+
+/// A layer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+    /// The identifier, which is absent for the layers of dies.
+    pub id: Option<Spanned<String>>,
+    /// The height.
+    pub height: Spanned<f64>,
+    /// The identifier of the material.
+    pub material: Spanned<String>,
+}
+
+/// A die.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Die {
+    /// The identifier.
+    pub id: Spanned<String>,
+    /// The list of layers ordered from bottom to top.
+    pub layers: Vec<Spanned<Layer>>,
+    /// The position of the source layer in the list of layers.
+    pub source: usize,
+}
+
+/// The dimensions of a stack.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dimensions {
+    /// The length of the chip.
+    pub chip_length: Spanned<f64>,
+    /// The width of the chip.
+    pub chip_width: Spanned<f64>,
+    /// The length of a cell.
+    pub cell_length: Spanned<f64>,
+    /// The width of a cell.
+    pub cell_width: Spanned<f64>,
+}
+
+/// An element of a stack.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StackElement {
+    /// A channel.
+    Channel {
+        /// The identifier.
+        id: Spanned<String>,
+    },
+    /// A die.
+    Die {
+        /// The identifier.
+        id: Spanned<String>,
+        /// The identifier of the die.
+        die: Spanned<String>,
+        /// The path to the floorplan file.
+        floorplan: Spanned<String>,
+    },
+    /// A layer.
+    Layer {
+        /// The identifier.
+        id: Spanned<String>,
+        /// The identifier of the layer.
+        layer: Spanned<String>,
+    },
+}
+
+/// A solver.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Solver {
+    /// The type of analysis.
+    pub kind: Spanned<AnalysisType>,
+    /// The time step in the case of transient analysis.
+    pub step_time: Option<Spanned<f64>>,
+    /// The time slot in the case of transient analysis.
+    pub slot_time: Option<Spanned<f64>>,
+    /// The initial temperature.
+    pub initial_temperature: Spanned<f64>,
+}
+
+/// An output instruction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputInstruction {
+    /// The type.
+    pub kind: Spanned<OutputType>,
+    /// The identifier of the stack element.
+    pub element: Spanned<String>,
+    /// The path to the output file.
+    pub file: Spanned<String>,
+    /// The quantity.
+    pub quantity: Option<Spanned<OutputQuantity>>,
+    /// The instant.
+    pub instant: Option<Spanned<OutputInstant>>,
+}
+
+/// A type of output.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OutputType {
+    /// The temperature of the cell containing a point.
+    Tcell {
+        /// The abscissa of the point.
+        x: Spanned<f64>,
+        /// The ordinate of the point.
+        y: Spanned<f64>,
+    },
+    /// The temperature of the elements of a floorplan.
+    Tflp,
+    /// The temperature of an element of a floorplan.
+    Tflpel(Spanned<String>),
+    /// The thermal map of a stack element.
+    Tmap,
+    /// The power map of a stack element.
+    Pmap,
+    /// The temperature of the coolant leaving a channel.
+    Tcoolant,
+}
+
+const LEXICON: Lexicon = Lexicon {
+    keywords: &[
+        "2rm", "4rm", "ambient", "area", "average", "bottom", "capacity", "cell", "channel",
+        "chip", "coefficient", "conductivity", "connection", "coolant", "darcy", "diameter",
+        "die", "dimensions", "distribution", "final", "first", "floorplan", "flow", "heat",
+        "height", "incoming", "initial", "inline", "last", "layer", "length", "material",
+        "maximum", "microchannel", "minimum", "output", "pin", "pinfin", "pitch", "Pmap", "rate",
+        "side", "sink", "slot", "solver", "source", "spreader", "stack", "staggered", "state",
+        "steady", "step", "T", "temperature", "Tcoolant", "Tflp", "Tflpel", "thermal", "to",
+        "top", "Tmap", "transfer", "transient", "velocity", "volumetric", "wall", "width",
+    ],
+    symbols: "(),.:;",
+    paths: true,
+};
+
+pub fn parse(input: &str) -> Result<Stack> {
+    let mut stream = try!(Stream::new(input, &LEXICON));
+    let stack = try!(parse_stack(&mut stream));
+    try!(check(&stack));
+    Ok(stack)
+}
+
+fn parse_stack(stream: &mut Stream) -> Result<Stack> {
+    let mut materials = vec![try!(parse_material(stream))];
+    while stream.is_keyword("material") {
+        materials.push(try!(parse_material(stream)));
+    }
+
+    let heat_sink = if stream.is_keyword("heat") || stream.is_keyword("connection") {
+        Some(try!(parse_heat_sink(stream)))
+    } else {
+        None
+    };
+
+    let channel = if stream.is_keyword("microchannel") || stream.is_keyword("pinfin") {
+        Some(try!(parse_channel(stream)))
+    } else {
+        None
+    };
+
+    let mut layers = vec![];
+    while stream.is_keyword("layer") {
+        layers.push(try!(parse_layer(stream)));
+    }
+
+    let mut dies = vec![try!(parse_die(stream))];
+    while stream.is_keyword("die") {
+        dies.push(try!(parse_die(stream)));
+    }
+
+    let dimensions = try!(parse_dimensions(stream));
+
+    try!(stream.keyword("stack"));
+    try!(stream.symbol(':'));
+    let mut elements = vec![try!(parse_stack_element(stream))];
+    while ["channel", "die", "layer"].iter().any(|keyword| stream.is_keyword(keyword)) {
+        elements.push(try!(parse_stack_element(stream)));
+    }
+
+    let solver = try!(parse_solver(stream));
+
+    let mut output = vec![];
+    if stream.is_keyword("output") {
+        try!(stream.keyword("output"));
+        try!(stream.symbol(':'));
+        output.push(try!(parse_output_instruction(stream)));
+        while !stream.is_end() {
+            output.push(try!(parse_output_instruction(stream)));
+        }
+    }
+
+    if !stream.is_end() {
+        return stream.expected("end of input");
+    }
+
+    Ok(Stack {
+        materials: materials,
+        heat_sink: heat_sink,
+        channel: channel,
+        layers: layers,
+        dies: dies,
+        dimensions: dimensions,
+        elements: elements,
+        solver: solver,
+        output: output,
+    })
+}
+
+fn parse_material(stream: &mut Stream) -> Result<Spanned<Material>> {
+    let start = try!(stream.keyword("material"));
+    let id = try!(stream.identifier());
+    try!(stream.symbol(':'));
+    let thermal_conductivity = try!(stream.field(&["thermal", "conductivity"], ';'));
+    let volumetric_heat_capacity = try!(stream.field(&["volumetric", "heat", "capacity"], ';'));
+    Ok(stream.spanned(start, Material {
+        id: id,
+        thermal_conductivity: thermal_conductivity,
+        volumetric_heat_capacity: volumetric_heat_capacity,
+    }))
+}
+
+fn parse_heat_sink(stream: &mut Stream) -> Result<Spanned<HeatSink>> {
+    let start = stream.span();
+    let (model, sink, spreader) = if stream.is_keyword("heat") {
+        try!(stream.keywords(&["heat", "sink"]));
+        try!(stream.symbol(':'));
+        let sink = try!(parse_heat_sink_part(stream, "sink"));
+        let spreader = try!(parse_heat_sink_part(stream, "spreader"));
+        (HeatSinkModel::Traditional, Some(sink), Some(spreader))
+    } else {
+        try!(stream.keywords(&["connection", "to", "ambient"]));
+        try!(stream.symbol(':'));
+        (HeatSinkModel::ConnectionToAmbient, None, None)
+    };
+    let heat_transfer_coefficient = try!(stream.field(&["heat", "transfer", "coefficient"], ';'));
+    let ambient_temperature = try!(stream.field(&["ambient", "temperature"], ';'));
+    Ok(stream.spanned(start, HeatSink {
+        model: model,
+        heat_transfer_coefficient: heat_transfer_coefficient,
+        ambient_temperature: ambient_temperature,
+        sink: sink,
+        spreader: spreader,
+    }))
+}
+
+fn parse_heat_sink_part(stream: &mut Stream, name: &'static str)
+                        -> Result<Spanned<HeatSinkPart>> {
+
+    let start = try!(stream.keyword(name));
+    let height = try!(stream.field(&["height"], ','));
+    let area = try!(stream.field(&["area"], ','));
+    try!(stream.keyword("material"));
+    let material = try!(stream.identifier());
+    try!(stream.symbol(';'));
+    Ok(stream.spanned(start, HeatSinkPart { height: height, area: area, material: material }))
+}
+
+fn parse_channel(stream: &mut Stream) -> Result<Spanned<Channel>> {
+    let start = stream.span();
+    let channel = if stream.is_keyword("pinfin") {
+        let model = try!(stream.keyword("pinfin"));
+        try!(stream.symbol(':'));
+        let height = try!(stream.field(&["height"], ';'));
+        let pin_diameter = try!(stream.field(&["pin", "diameter"], ';'));
+        let pin_pitch = try!(stream.field(&["pin", "pitch"], ';'));
+        try!(stream.keywords(&["pin", "distribution"]));
+        let distribution = try!(stream.choose(&["inline", "staggered"]));
+        try!(stream.symbol(';'));
+        try!(stream.keywords(&["pin", "material"]));
+        let wall_material = try!(stream.identifier());
+        try!(stream.symbol(';'));
+        let darcy_velocity = try!(stream.field(&["darcy", "velocity"], ';'));
+        let (volumetric_heat_capacity, temperature) = try!(parse_coolant(stream));
+        Channel {
+            model: Spanned {
+                node: match distribution.node {
+                    "inline" => ChannelModel::PinFinInline,
+                    _ => ChannelModel::PinFinStaggered,
+                },
+                span: model,
+            },
+            height: height,
+            channel_length: None,
+            wall_length: None,
+            first_wall_length: None,
+            last_wall_length: None,
+            pin_diameter: Some(pin_diameter),
+            pin_pitch: Some(pin_pitch),
+            wall_material: wall_material,
+            coolant: Coolant {
+                flow_rate: None,
+                darcy_velocity: Some(darcy_velocity),
+                side_heat_transfer_coefficient: None,
+                top_heat_transfer_coefficient: None,
+                bottom_heat_transfer_coefficient: None,
+                volumetric_heat_capacity: volumetric_heat_capacity,
+                temperature: temperature,
+            },
+        }
+    } else {
+        let model_start = try!(stream.keyword("microchannel"));
+        let model = match try!(stream.choose(&["4rm", "2rm"])).node {
+            "4rm" => ChannelModel::Microchannel4RM,
+            _ => ChannelModel::Microchannel2RM,
+        };
+        let model = stream.spanned(model_start, model);
+        try!(stream.symbol(':'));
+        let height = try!(stream.field(&["height"], ';'));
+        let channel_length = try!(stream.field(&["channel", "length"], ';'));
+        let wall_length = try!(stream.field(&["wall", "length"], ';'));
+        let (mut first_wall_length, mut last_wall_length) = (None, None);
+        if model.node == ChannelModel::Microchannel4RM {
+            if stream.is_keyword("first") {
+                first_wall_length = Some(try!(stream.field(&["first", "wall", "length"], ';')));
+            }
+            if stream.is_keyword("last") {
+                last_wall_length = Some(try!(stream.field(&["last", "wall", "length"], ';')));
+            }
+        }
+        try!(stream.keywords(&["wall", "material"]));
+        let wall_material = try!(stream.identifier());
+        try!(stream.symbol(';'));
+        let flow_rate = try!(stream.field(&["coolant", "flow", "rate"], ';'));
+        try!(stream.keywords(&["coolant", "heat", "transfer", "coefficient"]));
+        let (side, top, bottom);
+        if model.node == ChannelModel::Microchannel4RM && stream.is_keyword("side") {
+            side = Some(try!(stream.field(&["side"], ',')));
+            top = try!(stream.field(&["top"], ','));
+            bottom = try!(stream.field(&["bottom"], ';'));
+        } else if model.node == ChannelModel::Microchannel2RM && stream.is_keyword("top") {
+            side = None;
+            top = try!(stream.field(&["top"], ','));
+            bottom = try!(stream.field(&["bottom"], ';'));
+        } else {
+            let value = try!(stream.number());
+            try!(stream.symbol(';'));
+            side = match model.node {
+                ChannelModel::Microchannel4RM => Some(value.clone()),
+                _ => None,
+            };
+            top = value.clone();
+            bottom = value;
+        }
+        let (volumetric_heat_capacity, temperature) = try!(parse_coolant(stream));
+        Channel {
+            model: model,
+            height: height,
+            channel_length: Some(channel_length),
+            wall_length: Some(wall_length),
+            first_wall_length: first_wall_length,
+            last_wall_length: last_wall_length,
+            pin_diameter: None,
+            pin_pitch: None,
+            wall_material: wall_material,
+            coolant: Coolant {
+                flow_rate: Some(flow_rate),
+                darcy_velocity: None,
+                side_heat_transfer_coefficient: side,
+                top_heat_transfer_coefficient: Some(top),
+                bottom_heat_transfer_coefficient: Some(bottom),
+                volumetric_heat_capacity: volumetric_heat_capacity,
+                temperature: temperature,
+            },
+        }
+    };
+    Ok(stream.spanned(start, channel))
+}
+
+fn parse_coolant(stream: &mut Stream) -> Result<(Spanned<f64>, Spanned<f64>)> {
+    let volumetric_heat_capacity =
+        try!(stream.field(&["coolant", "volumetric", "heat", "capacity"], ';'));
+    let temperature = try!(stream.field(&["coolant", "incoming", "temperature"], ';'));
+    Ok((volumetric_heat_capacity, temperature))
+}
+
+fn parse_layer(stream: &mut Stream) -> Result<Spanned<Layer>> {
+    let start = try!(stream.keyword("layer"));
+    let id = try!(stream.identifier());
+    try!(stream.symbol(':'));
+    let height = try!(stream.field(&["height"], ';'));
+    try!(stream.keyword("material"));
+    let material = try!(stream.identifier());
+    try!(stream.symbol(';'));
+    Ok(stream.spanned(start, Layer { id: Some(id), height: height, material: material }))
+}
+
+fn parse_die(stream: &mut Stream) -> Result<Spanned<Die>> {
+    let start = try!(stream.keyword("die"));
+    let id = try!(stream.identifier());
+    try!(stream.symbol(':'));
+    let mut layers = vec![];
+    while stream.is_keyword("layer") {
+        layers.push(try!(parse_die_layer(stream, "layer")));
+    }
+    let source = layers.len();
+    layers.push(try!(parse_die_layer(stream, "source")));
+    while stream.is_keyword("layer") {
+        layers.push(try!(parse_die_layer(stream, "layer")));
+    }
+    layers.reverse();
+    let source = layers.len() - 1 - source;
+    Ok(stream.spanned(start, Die { id: id, layers: layers, source: source }))
+}
+
+fn parse_die_layer(stream: &mut Stream, name: &'static str) -> Result<Spanned<Layer>> {
+    let start = try!(stream.keyword(name));
+    let height = try!(stream.number());
+    let material = try!(stream.identifier());
+    try!(stream.symbol(';'));
+    Ok(stream.spanned(start, Layer { id: None, height: height, material: material }))
+}
+
+fn parse_dimensions(stream: &mut Stream) -> Result<Spanned<Dimensions>> {
+    let start = try!(stream.keyword("dimensions"));
+    try!(stream.symbol(':'));
+    let chip_length = try!(stream.field(&["chip", "length"], ','));
+    let chip_width = try!(stream.field(&["width"], ';'));
+    let cell_length = try!(stream.field(&["cell", "length"], ','));
+    let cell_width = try!(stream.field(&["width"], ';'));
+    Ok(stream.spanned(start, Dimensions {
+        chip_length: chip_length,
+        chip_width: chip_width,
+        cell_length: cell_length,
+        cell_width: cell_width,
+    }))
+}
+
+fn parse_stack_element(stream: &mut Stream) -> Result<Spanned<StackElement>> {
+    let start = stream.span();
+    let element = match try!(stream.choose(&["layer", "channel", "die"])).node {
+        "layer" => {
+            let id = try!(stream.identifier());
+            let layer = try!(stream.identifier());
+            StackElement::Layer { id: id, layer: layer }
+        },
+        "channel" => StackElement::Channel { id: try!(stream.identifier()) },
+        _ => {
+            let id = try!(stream.identifier());
+            let die = try!(stream.identifier());
+            try!(stream.keyword("floorplan"));
+            let floorplan = try!(stream.path());
+            StackElement::Die { id: id, die: die, floorplan: floorplan }
+        },
+    };
+    try!(stream.symbol(';'));
+    Ok(stream.spanned(start, element))
+}
+
+fn parse_solver(stream: &mut Stream) -> Result<Spanned<Solver>> {
+    let start = try!(stream.keyword("solver"));
+    try!(stream.symbol(':'));
+    let kind = try!(stream.choose(&["steady", "transient"]));
+    let (step_time, slot_time) = if kind.node == "transient" {
+        let step_time = try!(stream.field(&["step"], ','));
+        let slot_time = try!(stream.field(&["slot"], ';'));
+        (Some(step_time), Some(slot_time))
+    } else {
+        try!(stream.symbol(';'));
+        (None, None)
+    };
+    let kind = Spanned {
+        node: match kind.node {
+            "steady" => AnalysisType::Steady,
+            _ => AnalysisType::Transient,
+        },
+        span: kind.span,
+    };
+    let initial_temperature = try!(stream.field(&["initial", "temperature"], ';'));
+    Ok(stream.spanned(start, Solver {
+        kind: kind,
+        step_time: step_time,
+        slot_time: slot_time,
+        initial_temperature: initial_temperature,
+    }))
+}
+
+fn parse_output_instruction(stream: &mut Stream) -> Result<Spanned<OutputInstruction>> {
+    let start = stream.span();
+    let name = try!(stream.choose(&["T", "Tflp", "Tflpel", "Tmap", "Pmap", "Tcoolant"]));
+    try!(stream.symbol('('));
+    let element = try!(stream.identifier());
+    let kind = match name.node {
+        "T" => {
+            try!(stream.symbol(','));
+            let x = try!(stream.number());
+            try!(stream.symbol(','));
+            let y = try!(stream.number());
+            OutputType::Tcell { x: x, y: y }
+        },
+        "Tflpel" => {
+            try!(stream.symbol('.'));
+            OutputType::Tflpel(try!(stream.identifier()))
+        },
+        "Tflp" => OutputType::Tflp,
+        "Tmap" => OutputType::Tmap,
+        "Pmap" => OutputType::Pmap,
+        _ => OutputType::Tcoolant,
+    };
+    let kind = Spanned { node: kind, span: name.span };
+    try!(stream.symbol(','));
+    let file = try!(stream.path());
+    let quantity = match kind.node {
+        OutputType::Tflp | OutputType::Tflpel(_) | OutputType::Tcoolant => {
+            try!(stream.symbol(','));
+            let quantity = try!(stream.choose(&["maximum", "minimum", "average"]));
+            Some(Spanned {
+                node: match quantity.node {
+                    "maximum" => OutputQuantity::Maximum,
+                    "minimum" => OutputQuantity::Minimum,
+                    _ => OutputQuantity::Average,
+                },
+                span: quantity.span,
+            })
+        },
+        _ => None,
+    };
+    let instant = if stream.is_symbol(',') {
+        try!(stream.symbol(','));
+        let instant = try!(stream.choose(&["step", "slot", "final"]));
+        Some(Spanned {
+            node: match instant.node {
+                "step" => OutputInstant::Step,
+                "slot" => OutputInstant::Slot,
+                _ => OutputInstant::Final,
+            },
+            span: instant.span,
+        })
+    } else {
+        None
+    };
+    try!(stream.symbol(')'));
+    try!(stream.symbol(';'));
+    Ok(stream.spanned(start, OutputInstruction {
+        kind: kind,
+        element: element,
+        file: file,
+        quantity: quantity,
+        instant: instant,
+    }))
+}
+
+fn check(stack: &Stack) -> Result<()> {
+    let mut materials = HashSet::new();
+    for material in &stack.materials {
+        try!(declare(&mut materials, &material.id, "material"));
+    }
+
+    if let Some(ref heat_sink) = stack.heat_sink {
+        for part in heat_sink.sink.iter().chain(heat_sink.spreader.iter()) {
+            try!(refer(&materials, &part.material, "material"));
+        }
+    }
+    if let Some(ref channel) = stack.channel {
+        try!(refer(&materials, &channel.wall_material, "material"));
+    }
+
+    let mut layers = HashSet::new();
+    for layer in &stack.layers {
+        if let Some(ref id) = layer.id {
+            try!(declare(&mut layers, id, "layer"));
+        }
+        try!(refer(&materials, &layer.material, "material"));
+    }
+
+    let mut dies = HashSet::new();
+    for die in &stack.dies {
+        try!(declare(&mut dies, &die.id, "die"));
+        for layer in &die.layers {
+            try!(refer(&materials, &layer.material, "material"));
+        }
+    }
+
+    let mut elements = HashSet::new();
+    for element in &stack.elements {
+        match element.node {
+            StackElement::Channel { ref id } => {
+                try!(declare(&mut elements, id, "stack element"));
+                if stack.channel.is_none() {
+                    raise!(lexer::fail(id.span, "no channel is declared".to_string()));
+                }
+            },
+            StackElement::Die { ref id, ref die, .. } => {
+                try!(declare(&mut elements, id, "stack element"));
+                try!(refer(&dies, die, "die"));
+            },
+            StackElement::Layer { ref id, ref layer } => {
+                try!(declare(&mut elements, id, "stack element"));
+                try!(refer(&layers, layer, "layer"));
+            },
+        }
+    }
+    if !stack.elements.iter().any(|element| is_die(&element.node)) {
+        raise!(lexer::fail(stack.elements[0].span,
+                           "the stack should contain at least one die".to_string()));
+    }
+    for (i, element) in stack.elements.iter().enumerate() {
+        if !is_channel(&element.node) {
+            continue;
+        }
+        if i == 0 {
+            raise!(lexer::fail(element.span, "a channel cannot be the top element".to_string()));
+        }
+        if i + 1 == stack.elements.len() {
+            raise!(lexer::fail(element.span,
+                               "a channel cannot be the bottom element".to_string()));
+        }
+        if is_channel(&stack.elements[i - 1].node) {
+            raise!(lexer::fail(element.span, "two channels cannot be adjacent".to_string()));
+        }
+    }
+
+    let solver = &stack.solver;
+    if let (&Some(ref step_time), &Some(ref slot_time)) = (&solver.step_time, &solver.slot_time) {
+        if step_time.node <= 0.0 {
+            raise!(lexer::fail(step_time.span, "the time step should be positive".to_string()));
+        }
+        if slot_time.node < step_time.node {
+            raise!(lexer::fail(slot_time.span,
+                               "the time slot should not be shorter than the time step"
+                               .to_string()));
+        }
+    }
+
+    for instruction in &stack.output {
+        let id = &instruction.element;
+        let element = match stack.elements.iter().find(|element| element_id(element) == &id.node) {
+            Some(element) => &element.node,
+            _ => raise!(lexer::fail(id.span, format!("unknown stack element `{}`", id.node))),
+        };
+        match instruction.kind.node {
+            OutputType::Tflp | OutputType::Tflpel(_) if !is_die(element) => {
+                raise!(lexer::fail(id.span, format!("the stack element `{}` should be a die",
+                                                    id.node)));
+            },
+            OutputType::Tcoolant if !is_channel(element) => {
+                raise!(lexer::fail(id.span, format!("the stack element `{}` should be a channel",
+                                                    id.node)));
+            },
+            _ => {},
+        }
+    }
+
+    Ok(())
+}
+
+fn declare<'l>(ids: &mut HashSet<&'l str>, id: &'l Spanned<String>, what: &str) -> Result<()> {
+    if !ids.insert(&id.node) {
+        raise!(lexer::fail(id.span, format!("the {} `{}` is already declared", what, id.node)));
+    }
+    Ok(())
+}
+
+fn refer(ids: &HashSet<&str>, id: &Spanned<String>, what: &str) -> Result<()> {
+    if !ids.contains(&id.node as &str) {
+        raise!(lexer::fail(id.span, format!("unknown {} `{}`", what, id.node)));
+    }
+    Ok(())
+}
+
+fn element_id(element: &Spanned<StackElement>) -> &String {
+    match element.node {
+        StackElement::Channel { ref id } |
+        StackElement::Die { ref id, .. } |
+        StackElement::Layer { ref id, .. } => &id.node,
+    }
+}
+
+#[inline]
+fn is_channel(element: &StackElement) -> bool {
+    match *element {
+        StackElement::Channel { .. } => true,
+        _ => false,
+    }
+}
+
+#[inline]
+fn is_die(element: &StackElement) -> bool {
+    match *element {
+        StackElement::Die { .. } => true,
+        _ => false,
+    }
+}
@@ -1,11 +1,13 @@
 use ffi;
-use matrix::format::Compressed;
+use matrix::format::{Compressed, Conventional};
 use matrix::{Matrix, Size};
 use std::marker::PhantomData;
 use std::mem;
 
+use floorplan;
 use stack::Stack;
-use {Raw, Result};
+use thermal_grid::ThermalGrid;
+use {Error, Raw, Result};
 
 /// A power grid.
 pub struct PowerGrid<'l> {
@@ -37,7 +39,7 @@ impl<'l> PowerGrid<'l> {
 
                     let block = match result {
                         Some(block) => block,
-                        _ => raise!("failed to convert a floorplan matrix"),
+                        _ => raise!(Error::Conversion("floorplan matrix")),
                     };
 
                     let (i0, j0) = (k * cells / depth, matrix.columns());
@@ -52,6 +54,88 @@ impl<'l> PowerGrid<'l> {
 
         Ok(matrix)
     }
+
+    /// Assemble the power values of the processing elements into a matrix
+    /// whose rows correspond to the processing elements and columns to the
+    /// time slots. The rows are ordered as the columns of the distribution
+    /// matrix.
+    pub fn power(&self) -> Result<Conventional<f64>> {
+        let depth = self.raw.NLayers as usize;
+
+        let layers = slice!(self.raw.LayersProfile, depth);
+        let floorplans = slice!(self.raw.FloorplansProfile, depth);
+
+        let mut traces = vec![];
+        for k in 0..depth {
+            match layers[k] {
+                ffi::TDICE_LAYER_SOURCE | ffi::TDICE_LAYER_SOURCE_CONNECTED_TO_AMBIENT => unsafe {
+                    for element in floorplan::new(&*floorplans[k]).elements {
+                        traces.push(element.power);
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        let (rows, columns) = (traces.len(), traces.first().map_or(0, |trace| trace.len()));
+        if traces.iter().any(|trace| trace.len() != columns) {
+            raise!("the power values of the processing elements have different lengths");
+        }
+
+        let mut matrix = Conventional::new((rows, columns));
+        for (i, trace) in traces.iter().enumerate() {
+            for (j, &value) in trace.iter().enumerate() {
+                matrix.values[j * rows + i] = value;
+            }
+        }
+
+        Ok(matrix)
+    }
+
+    /// Compute the vector of the heat flowing in from the ambient and from the
+    /// coolant entering the channels.
+    pub fn boundary(&self, grid: &ThermalGrid, stack: &Stack) -> Result<Vec<f64>> {
+        let (depth, cells) = (self.raw.NLayers as usize, self.raw.NCells as usize);
+        let (rows, columns) = (stack.dimensions.rows(), stack.dimensions.columns());
+
+        let layers = slice!(self.raw.LayersProfile, depth);
+
+        let mut vector = vec![0.0; cells];
+        for k in 0..depth {
+            match layers[k] {
+                ffi::TDICE_LAYER_SOLID_CONNECTED_TO_AMBIENT |
+                ffi::TDICE_LAYER_SOURCE_CONNECTED_TO_AMBIENT |
+                ffi::TDICE_LAYER_SINK => {
+                    let temperature = unsafe { (*self.raw.HeatSink).AmbientTemperature };
+                    for i in 0..rows {
+                        for j in 0..columns {
+                            vector[(k * rows + i) * columns + j] +=
+                                temperature * grid.conductance_top(stack, k, i, j);
+                        }
+                    }
+                },
+                ffi::TDICE_LAYER_CHANNEL_4RM |
+                ffi::TDICE_LAYER_CHANNEL_2RM |
+                ffi::TDICE_LAYER_PINFINS_INLINE |
+                ffi::TDICE_LAYER_PINFINS_STAGGERED => unsafe {
+                    let channel = self.raw.Channel;
+                    let model = (*channel).ChannelModel;
+                    let temperature = (*channel).Coolant.TIn;
+                    for j in 0..columns {
+                        if model == ffi::TDICE_CHANNEL_MODEL_MC_4RM && j % 2 == 0 {
+                            continue;
+                        }
+                        let term = get_convective_term(channel, stack.raw().Dimensions, k as _,
+                                                       0, j as _);
+                        vector[k * rows * columns + j] = 2.0 * term * temperature;
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        Ok(vector)
+    }
 }
 
 impl<'l> Drop for PowerGrid<'l> {
@@ -70,8 +154,15 @@ pub unsafe fn new<'l>(stack: &'l Stack) -> Result<PowerGrid<'l>> {
     let cells = ffi::get_number_of_cells(stack.Dimensions);
     let layers = ffi::get_number_of_layers(stack.Dimensions);
 
-    success!(ffi::power_grid_build(&mut raw, layers, cells), "build the power grid");
+    success!(ffi::power_grid_build(&mut raw, layers, cells), Build, object: "power grid");
     ffi::fill_power_grid(&mut raw, &stack.StackElements as *const _ as *mut _);
 
     Ok(PowerGrid { raw: raw, phantom: PhantomData })
 }
+
+// The routine is a part of 3D-ICE but is not exposed by threed-ice-sys.
+extern "C" {
+    fn get_convective_term(channel: *mut ffi::Channel_t, dimensions: *mut ffi::Dimensions_t,
+                           layer_index: ffi::CellIndex_t, row_index: ffi::CellIndex_t,
+                           column_index: ffi::CellIndex_t) -> f64;
+}
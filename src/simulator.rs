@@ -0,0 +1,217 @@
+use ffi;
+use matrix::format::Compressed;
+use matrix::Size;
+
+use analysis::AnalysisType;
+use output::OutputInstant;
+use system::{self, System};
+use {Raw, Result};
+
+/// A transient temperature simulator.
+///
+/// The simulator performs the backward Euler method using the step time, slot
+/// time, and initial temperature prescribed by the analysis of a system. The
+/// power dissipation is assumed to be constant within each time slot.
+pub struct Simulator<'l> {
+    system: &'l System,
+    output: bool,
+    capacitance: Vec<f64>,
+    distribution: Compressed<f64>,
+    boundary: Vec<f64>,
+    source: Vec<f64>,
+    temperature: Vec<f64>,
+    initial: f64,
+    step_time: f64,
+    slot_length: usize,
+    steps: usize,
+}
+
+impl<'l> Simulator<'l> {
+    /// Create a simulator for a system.
+    pub fn new(system: &'l System) -> Result<Simulator<'l>> {
+        unsafe { new(system) }
+    }
+
+    /// Return the number of processing elements.
+    #[inline]
+    pub fn elements(&self) -> usize {
+        self.distribution.columns()
+    }
+
+    /// Return the number of thermal nodes.
+    #[inline]
+    pub fn nodes(&self) -> usize {
+        self.temperature.len()
+    }
+
+    /// Return the current temperature of the thermal nodes.
+    #[inline]
+    pub fn temperature(&self) -> &[f64] {
+        &self.temperature
+    }
+
+    /// Return the simulated time.
+    #[inline]
+    pub fn time(&self) -> f64 {
+        self.steps as f64 * self.step_time
+    }
+
+    /// Enable the output prescribed by the system.
+    ///
+    /// The function creates the output files and writes their headers. After
+    /// that, the simulator appends to the files at the end of each time step
+    /// or time slot, and `finish` produces the final output. The format is the
+    /// one of 3D-ICE.
+    ///
+    /// The names of the output files are used by 3D-ICE as they are given in
+    /// the stack description; therefore, relative names are resolved with
+    /// respect to the current working directory of the process, not to the
+    /// location of the stack-description file. In addition, if there are
+    /// `Tmap` or `Pmap` instructions, the coordinates of the cells are written
+    /// into `xaxis.txt` and `yaxis.txt` in the current working directory.
+    pub fn enable_output(&mut self) -> Result<()> {
+        unsafe {
+            let prefix = str_to_cstr!("% ");
+            success!(ffi::generate_output_headers(self.system.output.raw() as *const _ as *mut _,
+                                                  self.system.stack.raw().Dimensions,
+                                                  prefix.as_ptr() as *mut _),
+                     "generate the output headers");
+        }
+        self.output = true;
+        Ok(())
+    }
+
+    /// Produce the final output if enabled.
+    ///
+    /// The power in the output is the one of the last time step.
+    pub fn finish(&mut self) -> Result<()> {
+        let source = self.source.clone();
+        self.write(&source, OutputInstant::Final)
+    }
+
+    /// Restore the initial temperature and the initial time.
+    pub fn reset(&mut self) {
+        for value in &mut self.temperature {
+            *value = self.initial;
+        }
+        self.source.copy_from_slice(&self.boundary);
+        self.steps = 0;
+    }
+
+    /// Advance the simulation by one time step.
+    ///
+    /// The power vector should have one entry per processing element, and the
+    /// elements should be ordered as the columns of the distribution matrix.
+    pub fn step(&mut self, power: &[f64]) -> Result<()> {
+        let source = try!(self.source(power));
+        self.advance(&source)
+    }
+
+    /// Advance the simulation by one time slot.
+    ///
+    /// The power vector is the same as the one of `step`.
+    pub fn slot(&mut self, power: &[f64]) -> Result<()> {
+        let source = try!(self.source(power));
+        for _ in 0..self.slot_length {
+            try!(self.advance(&source));
+        }
+        self.write(&source, OutputInstant::Slot)
+    }
+
+    /// Advance the simulation by a number of time slots.
+    ///
+    /// The power profile is a matrix whose rows correspond to the processing
+    /// elements and columns to the time slots; the data are stored in the
+    /// column-major order. The result contains the temperature of the thermal
+    /// nodes at the end of each time slot stored in the same manner.
+    pub fn run(&mut self, power: &[f64]) -> Result<Vec<f64>> {
+        let (elements, nodes) = (self.elements(), self.nodes());
+        if elements == 0 || power.len() % elements != 0 {
+            raise!("the power profile has a wrong size");
+        }
+        let mut temperature = Vec::with_capacity(power.len() / elements * nodes);
+        for power in power.chunks(elements) {
+            try!(self.slot(power));
+            temperature.extend(&self.temperature);
+        }
+        Ok(temperature)
+    }
+
+    fn source(&self, power: &[f64]) -> Result<Vec<f64>> {
+        if power.len() != self.elements() {
+            raise!("the power vector has a wrong size");
+        }
+        let mut source = self.boundary.clone();
+        for (i, j, &value) in self.distribution.iter() {
+            source[i] += value * power[j];
+        }
+        Ok(source)
+    }
+
+    fn advance(&mut self, source: &[f64]) -> Result<()> {
+        let mut b = source.to_vec();
+        for (i, value) in b.iter_mut().enumerate() {
+            *value += self.capacitance[i] * self.temperature[i];
+        }
+        try!(unsafe {
+            system::solve(self.system, &self.system.analysis, &b, &mut self.temperature)
+        });
+        self.source.copy_from_slice(source);
+        self.steps += 1;
+        self.write(source, OutputInstant::Step)
+    }
+
+    fn write(&mut self, source: &[f64], instant: OutputInstant) -> Result<()> {
+        if !self.output {
+            return Ok(());
+        }
+        let instant = match instant {
+            OutputInstant::Final => ffi::TDICE_OUTPUT_INSTANT_FINAL,
+            OutputInstant::Slot => ffi::TDICE_OUTPUT_INSTANT_SLOT,
+            OutputInstant::Step => ffi::TDICE_OUTPUT_INSTANT_STEP,
+            OutputInstant::None => return Ok(()),
+        };
+        unsafe {
+            success!(ffi::generate_output(self.system.output.raw() as *const _ as *mut _,
+                                          self.system.stack.raw().Dimensions,
+                                          self.temperature.as_mut_ptr(),
+                                          source.as_ptr() as *mut _, self.time(), instant),
+                     "generate the output");
+        }
+        Ok(())
+    }
+}
+
+unsafe fn new<'l>(system: &'l System) -> Result<Simulator<'l>> {
+    if system.analysis.kind() != AnalysisType::Transient {
+        raise!("the analysis should be transient");
+    }
+
+    let step_time = system.analysis.step_time();
+    let initial = system.analysis.initial_temperature();
+
+    let mut capacitance = try!(system.capacitance()).values;
+    for value in &mut capacitance {
+        *value /= step_time;
+    }
+
+    let distribution = try!(system.distribution());
+    let boundary = try!(system.boundary());
+    try!(system::prepare(system, &system.analysis));
+
+    let nodes = ffi::get_number_of_cells(system.stack.raw().Dimensions) as usize;
+
+    Ok(Simulator {
+        system: system,
+        output: false,
+        capacitance: capacitance,
+        distribution: distribution,
+        source: boundary.clone(),
+        boundary: boundary,
+        temperature: vec![initial; nodes],
+        initial: initial,
+        step_time: step_time,
+        slot_length: system.analysis.slot_length(),
+        steps: 0,
+    })
+}
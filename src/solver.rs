@@ -0,0 +1,185 @@
+use matrix::format::Compressed;
+use matrix::format::compressed::Variant;
+
+use conjugate_gradient::ConjugateGradient;
+use multigrid::Multigrid;
+use Result;
+
+/// A solver of the linear systems arising in temperature analysis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Solver {
+    /// The direct solver based on the LU factorization computed by SuperLU.
+    Direct,
+    /// The preconditioned conjugate gradient method.
+    ///
+    /// The method requires the system matrix to be symmetric positive
+    /// definite, which is the case for stacks without microchannels, and keeps
+    /// in memory only the matrix and the preconditioner.
+    ConjugateGradient {
+        /// The preconditioner.
+        preconditioner: Preconditioner,
+        /// The tolerance on the norm of the residual relative to the one of the
+        /// right-hand side.
+        tolerance: f64,
+        /// The maximal number of iterations.
+        iterations: usize,
+    },
+    /// The geometric multigrid method.
+    ///
+    /// The method coarsens the rows and columns of the thermal grid within
+    /// each layer and performs V-cycles until convergence, which makes its
+    /// cost grow linearly with the number of thermal nodes. The system matrix
+    /// is not required to be symmetric.
+    Multigrid {
+        /// The tolerance on the norm of the residual relative to the one of the
+        /// right-hand side.
+        tolerance: f64,
+        /// The maximal number of V-cycles.
+        iterations: usize,
+    },
+}
+
+/// A preconditioner of an iterative solver.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Preconditioner {
+    /// The Jacobi preconditioner, that is, the diagonal of the matrix.
+    Jacobi,
+    /// The incomplete Cholesky factorization with no fill-in.
+    IncompleteCholesky,
+}
+
+impl Default for Solver {
+    #[inline]
+    fn default() -> Solver {
+        Solver::Direct
+    }
+}
+
+/// An iterative solver prepared for a particular matrix.
+pub enum Iterative {
+    ConjugateGradient(ConjugateGradient),
+    Multigrid(Multigrid),
+}
+
+impl Iterative {
+    /// Prepare a solver for a matrix of a grid with the given numbers of
+    /// layers, rows, and columns.
+    pub fn new(solver: Solver, matrix: &Compressed<f64>, grid: (usize, usize, usize))
+               -> Result<Iterative> {
+        Ok(match solver {
+            Solver::ConjugateGradient { preconditioner, tolerance, iterations } => {
+                Iterative::ConjugateGradient(try!(ConjugateGradient::new(matrix, preconditioner,
+                                                                         tolerance, iterations)))
+            },
+            Solver::Multigrid { tolerance, iterations } => {
+                Iterative::Multigrid(try!(Multigrid::new(matrix, grid, tolerance, iterations)))
+            },
+            Solver::Direct => raise!("the direct solver is not iterative"),
+        })
+    }
+
+    /// Solve the system for a right-hand side. The solution vector holds the
+    /// initial guess on entry.
+    pub fn solve(&self, b: &[f64], x: &mut [f64]) -> Result<()> {
+        match *self {
+            Iterative::ConjugateGradient(ref solver) => solver.solve(b, x),
+            Iterative::Multigrid(ref solver) => solver.solve(b, x),
+        }
+    }
+}
+
+/// A sparse matrix stored in the compressed-row format with the column
+/// indices sorted within each row.
+pub struct Sparse {
+    pub size: usize,
+    pub offsets: Vec<usize>,
+    pub indices: Vec<usize>,
+    pub values: Vec<f64>,
+}
+
+impl Sparse {
+    /// Convert a square matrix.
+    pub fn new(matrix: &Compressed<f64>) -> Result<Sparse> {
+        use matrix::Size;
+
+        let size = matrix.rows();
+        if size == 0 || size != matrix.columns() {
+            raise!("the matrix should be square and nonempty");
+        }
+
+        let mut entries = matrix.iter().collect::<Vec<_>>();
+        entries.sort_by(|&(i1, j1, _), &(i2, j2, _)| (i1, j1).cmp(&(i2, j2)));
+
+        let mut offsets = vec![0; size + 1];
+        let mut indices = Vec::with_capacity(entries.len());
+        let mut values = Vec::with_capacity(entries.len());
+        for (i, j, &value) in entries {
+            offsets[i + 1] += 1;
+            indices.push(j);
+            values.push(value);
+        }
+        for i in 0..size {
+            offsets[i + 1] += offsets[i];
+        }
+
+        Ok(Sparse { size: size, offsets: offsets, indices: indices, values: values })
+    }
+
+    /// Convert the matrix into the compressed-row format of the matrix crate.
+    pub fn compressed(&self) -> Compressed<f64> {
+        Compressed {
+            rows: self.size,
+            columns: self.size,
+            nonzeros: self.values.len(),
+            variant: Variant::Row,
+            values: self.values.clone(),
+            indices: self.indices.clone(),
+            offsets: self.offsets.clone(),
+        }
+    }
+
+    /// Return the entry at a position.
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        let (start, end) = (self.offsets[i], self.offsets[i + 1]);
+        match self.indices[start..end].binary_search(&j) {
+            Ok(k) => self.values[start + k],
+            _ => 0.0,
+        }
+    }
+
+    /// Return the diagonal.
+    pub fn diagonal(&self) -> Vec<f64> {
+        (0..self.size).map(|i| self.get(i, i)).collect()
+    }
+
+    /// Check if the matrix is symmetric up to rounding errors.
+    pub fn is_symmetric(&self) -> bool {
+        for i in 0..self.size {
+            for k in self.offsets[i]..self.offsets[i + 1] {
+                let (j, value) = (self.indices[k], self.values[k]);
+                let other = self.get(j, i);
+                if (value - other).abs() > 1e-12 * value.abs().max(other.abs()) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Compute `y = A x`.
+    pub fn multiply(&self, x: &[f64], y: &mut [f64]) {
+        for i in 0..self.size {
+            let mut sum = 0.0;
+            for k in self.offsets[i]..self.offsets[i + 1] {
+                sum += self.values[k] * x[self.indices[k]];
+            }
+            y[i] = sum;
+        }
+    }
+}
+
+/// Compute the dot product of two vectors.
+#[inline]
+pub fn dot(x: &[f64], y: &[f64]) -> f64 {
+    x.iter().zip(y).fold(0.0, |sum, (&x, &y)| sum + x * y)
+}
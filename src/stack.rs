@@ -1,12 +1,19 @@
 use ffi;
 use std::{fs, mem};
 use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use analysis::{self, Analysis};
+use channel::{self, Channel};
 use die::{self, Die};
 use dimensions::{self, Dimensions};
+use heat_sink::{self, HeatSink};
+use layer::{self, Layer};
+use material::{self, Material};
 use output::{self, Output};
-use {Raw, Result};
+use parser::{self, Span};
+use {Diagnostic, Error, Raw, Result};
 
 /// A three-dimensional stack.
 pub struct Stack {
@@ -14,7 +21,12 @@ pub struct Stack {
     pub dimensions: Dimensions,
     /// The list of elements.
     pub elements: Vec<StackElement>,
+    /// The list of layers declared outside dies.
+    pub layers: Vec<Layer>,
+    /// The list of materials.
+    pub materials: Vec<Material>,
 
+    id: usize,
     raw: ffi::StackDescription_t,
 }
 
@@ -22,13 +34,13 @@ pub struct Stack {
 #[derive(Clone, Debug, PartialEq)]
 pub enum StackElement {
     /// A channel.
-    Channel,
+    Channel(Channel),
     /// A die.
     Die(Die),
     /// A heat sink.
-    HeatSink,
+    HeatSink(HeatSink),
     /// A layer.
-    Layer,
+    Layer(Layer),
     /// An undefined element.
     None,
 }
@@ -41,9 +53,20 @@ impl Drop for Stack {
 
 implement_raw!(Stack, ffi::StackDescription_t);
 
+// The structures allocated by 3D-ICE are owned exclusively by the stack and do
+// not refer to any global state.
+unsafe impl Send for Stack {}
+
+/// The lock serializing the calls to the parser of 3D-ICE, which keeps its
+/// state in global variables.
+static PARSER: Mutex<()> = Mutex::new(());
+
+/// The counter of stacks, which gives each stack a unique identifier.
+static COUNTER: AtomicUsize = AtomicUsize::new(1);
+
 pub unsafe fn new(path: &Path) -> Result<(Stack, Analysis, Output)> {
     if fs::metadata(path).is_err() {
-        raise!("the stack-description file does not exist");
+        raise!(Error::MissingFile(path.to_path_buf()));
     }
 
     let mut raw = mem::uninitialized();
@@ -52,19 +75,74 @@ pub unsafe fn new(path: &Path) -> Result<(Stack, Analysis, Output)> {
     let mut analysis = try!(analysis::new());
     let mut output = try!(output::new());
 
-    success!(ffi::parse_stack_description_file(path_to_cstr!(path).as_ptr() as *mut _, &mut raw,
-                                               analysis.raw_mut(), output.raw_mut()),
-             "parse the stack-description file");
+    let name = path_to_cstr!(path);
+    let status = {
+        let _guard = PARSER.lock().unwrap_or_else(|error| error.into_inner());
+        ffi::parse_stack_description_file(name.as_ptr() as *mut _, &mut raw,
+                                          analysis.raw_mut(), output.raw_mut())
+    };
+    if status != ffi::TDICE_SUCCESS {
+        raise!(Error::Parse {
+            path: path.to_path_buf(),
+            status: status as i32,
+            diagnostics: diagnose(path),
+        });
+    }
+
+    output::extract(&mut output);
 
     let stack = Stack {
         dimensions: dimensions::new(raw.Dimensions),
         elements: extract_elements(&raw),
+        layers: layer::extract(&raw.Layers),
+        materials: extract_materials(&raw.Materials),
+        id: COUNTER.fetch_add(1, Ordering::SeqCst),
         raw: raw,
     };
 
     Ok((stack, analysis, output))
 }
 
+/// Find the problems that make 3D-ICE reject a stack description.
+///
+/// 3D-ICE prints its messages to the standard streams, which cannot be taken
+/// over without affecting the whole process. Instead, the description and its
+/// floorplans are checked by the parser of the crate, which locates the
+/// problems precisely. The result is empty if 3D-ICE rejects a description
+/// that the parser of the crate accepts.
+fn diagnose(path: &Path) -> Vec<Diagnostic> {
+    let stack = match parser::read_stack(path) {
+        Ok(stack) => stack,
+        Err(error) => return vec![diagnostic(error, path, None)],
+    };
+    let mut diagnostics = vec![];
+    let mut floorplans = vec![];
+    for element in &stack.elements {
+        if let parser::StackElement::Die { ref floorplan, .. } = element.node {
+            if floorplans.contains(&&floorplan.node) {
+                continue;
+            }
+            floorplans.push(&floorplan.node);
+            if let Err(error) = parser::read_floorplan(&floorplan.node) {
+                diagnostics.push(diagnostic(error, path, Some(floorplan.span)));
+            }
+        }
+    }
+    diagnostics
+}
+
+fn diagnostic(error: Error, path: &Path, span: Option<Span>) -> Diagnostic {
+    match error {
+        Error::Syntax(diagnostic) => diagnostic,
+        error => Diagnostic {
+            file: Some(path.to_path_buf()),
+            line: span.map(|span| span.line),
+            column: span.map(|span| span.column),
+            message: error.to_string(),
+        },
+    }
+}
+
 unsafe fn extract_elements(raw: &ffi::StackDescription_t) -> Vec<StackElement> {
     let mut elements = vec![];
 
@@ -74,16 +152,19 @@ unsafe fn extract_elements(raw: &ffi::StackDescription_t) -> Vec<StackElement> {
         let element = &(*cursor).Data;
         match element.Type {
             ffi::TDICE_STACK_ELEMENT_CHANNEL => {
-                elements.push(StackElement::Channel);
+                let channel = channel::new(&*element.Pointer.Channel(), &*raw.Dimensions);
+                elements.push(StackElement::Channel(channel));
             },
             ffi::TDICE_STACK_ELEMENT_DIE => {
-                elements.push(StackElement::Die(die::new(&*element.Pointer.Die())));
+                let die = die::new(&*element.Pointer.Die(), element.Offset as usize);
+                elements.push(StackElement::Die(die));
             },
             ffi::TDICE_STACK_ELEMENT_HEATSINK => {
-                elements.push(StackElement::HeatSink);
+                let heat_sink = heat_sink::new(&*element.Pointer.HeatSink());
+                elements.push(StackElement::HeatSink(heat_sink));
             },
             ffi::TDICE_STACK_ELEMENT_LAYER => {
-                elements.push(StackElement::Layer);
+                elements.push(StackElement::Layer(layer::new(&*element.Pointer.Layer())));
             },
             ffi::TDICE_STACK_ELEMENT_NONE => {
                 elements.push(StackElement::None);
@@ -94,3 +175,35 @@ unsafe fn extract_elements(raw: &ffi::StackDescription_t) -> Vec<StackElement> {
 
     elements
 }
+
+/// Return the identifier of a stack, which is unique within the process.
+#[inline]
+pub fn id(stack: &Stack) -> usize {
+    stack.id
+}
+
+pub unsafe fn extract_ids(raw: &ffi::StackDescription_t) -> Vec<String> {
+    let mut ids = vec![];
+
+    let mut cursor = raw.StackElements.First;
+    for _ in 0..raw.StackElements.Size {
+        assert!(!cursor.is_null());
+        ids.push(c_str_to_string!((*cursor).Data.Id));
+        cursor = (*cursor).Next;
+    }
+
+    ids
+}
+
+unsafe fn extract_materials(raw: &ffi::MaterialList_t) -> Vec<Material> {
+    let mut materials = vec![];
+
+    let mut cursor = raw.First;
+    for _ in 0..raw.Size {
+        assert!(!cursor.is_null());
+        materials.push(material::new(&(*cursor).Data));
+        cursor = (*cursor).Next;
+    }
+
+    materials
+}
@@ -0,0 +1,160 @@
+use matrix::Size;
+use matrix::format::{Compressed, Conventional};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use analysis;
+use conductance;
+use factorization::Factorization;
+use system::{self, System};
+use Result;
+
+/// A discretization of the transient problem in time.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Discretization {
+    /// The exact solution with the power held constant over each interval.
+    ///
+    /// The discretization relies on `Exponential` and, therefore, requires the
+    /// conductance matrix to be symmetric.
+    ZeroOrderHold,
+    /// The backward Euler method, which is the one used by 3D-ICE.
+    BackwardEuler,
+}
+
+/// A discrete-time state-space model.
+///
+/// The model is `x[k + 1] = A x[k] + B P[k]` and `y[k] = H x[k]` where `x` is
+/// the temperature of the thermal nodes relative to `offset`, `P` is the power
+/// of the processing elements over the `k`th interval, and `y` is the
+/// temperature of the processing elements relative to `H offset`.
+///
+/// The matrices are dense. In particular, `A` has as many rows and columns as
+/// there are thermal nodes, and the memory that the model takes grows
+/// quadratically with the number of nodes, which limits the model to small
+/// grids.
+#[derive(Clone, Debug)]
+pub struct StateSpace {
+    /// The state matrix with one row and one column per thermal node.
+    pub a: Conventional<f64>,
+    /// The input matrix with one row per thermal node and one column per
+    /// processing element.
+    pub b: Conventional<f64>,
+    /// The output matrix with one row per processing element and one column
+    /// per thermal node.
+    ///
+    /// The temperature of a processing element is the average of the
+    /// temperature of the thermal nodes weighted by the distribution matrix.
+    pub h: Conventional<f64>,
+    /// The temperature in the steady state with no power dissipated.
+    pub offset: Vec<f64>,
+    /// The sampling interval.
+    pub interval: f64,
+}
+
+impl StateSpace {
+    /// Return the number of processing elements.
+    #[inline]
+    pub fn elements(&self) -> usize {
+        self.b.columns()
+    }
+
+    /// Return the number of thermal nodes.
+    #[inline]
+    pub fn nodes(&self) -> usize {
+        self.a.rows()
+    }
+
+    /// Write the model into a MAT-file.
+    ///
+    /// The file is of version 4, which can be read by `load` in MATLAB and
+    /// Octave and by `scipy.io.loadmat` in Python. The variables are named `A`,
+    /// `B`, `H`, `offset`, and `interval`.
+    pub fn write<T: AsRef<Path>>(&self, path: T) -> Result<()> {
+        let mut buffer = vec![];
+        variable(&mut buffer, "A", (self.a.rows(), self.a.columns()), &self.a.values);
+        variable(&mut buffer, "B", (self.b.rows(), self.b.columns()), &self.b.values);
+        variable(&mut buffer, "H", (self.h.rows(), self.h.columns()), &self.h.values);
+        variable(&mut buffer, "offset", (self.offset.len(), 1), &self.offset);
+        variable(&mut buffer, "interval", (1, 1), &[self.interval]);
+        try!(try!(File::create(path)).write_all(&buffer));
+        Ok(())
+    }
+}
+
+pub unsafe fn new(system: &System, interval: f64, discretization: Discretization)
+                  -> Result<StateSpace> {
+    if !(interval > 0.0) {
+        raise!("the sampling interval should be positive");
+    }
+    let distribution = try!(system.distribution());
+    let (nodes, elements) = (distribution.rows(), distribution.columns());
+
+    let (a, b) = match discretization {
+        Discretization::ZeroOrderHold => try!(try!(system.exponential()).discretize(interval)),
+        Discretization::BackwardEuler => try!(backward_euler(system, &distribution, interval)),
+    };
+
+    let mut total = vec![0.0; elements];
+    for (_, j, &value) in distribution.iter() {
+        total[j] += value;
+    }
+    if total.iter().any(|&value| value == 0.0) {
+        raise!("each processing element should be distributed over the thermal nodes");
+    }
+    let mut h = Conventional::new((elements, nodes));
+    for (i, j, &value) in distribution.iter() {
+        h.values[i * elements + j] += value / total[j];
+    }
+
+    let offset = try!(system.steady_state(&vec![0.0; elements]));
+
+    Ok(StateSpace { a: a, b: b, h: h, offset: offset, interval: interval })
+}
+
+/// Compute `A = (C/dt + G)^(-1) C/dt` and `B = (C/dt + G)^(-1) D`.
+unsafe fn backward_euler(system: &System, distribution: &Compressed<f64>, interval: f64)
+                         -> Result<(Conventional<f64>, Conventional<f64>)> {
+    let (nodes, elements) = (distribution.rows(), distribution.columns());
+    let capacitance = try!(system.capacitance()).values;
+
+    let mut analysis = try!(analysis::new());
+    try!(analysis.set_transient(interval, interval));
+    let factorization = {
+        let grid = try!(system::grid(system));
+        try!(Factorization::new(&try!(conductance::assemble(&system.stack, &analysis, &grid))))
+    };
+
+    let mut a = Conventional::new((nodes, nodes));
+    for (i, &value) in capacitance.iter().enumerate() {
+        a.values[i * nodes + i] = value / interval;
+    }
+    try!(factorization.solve_many(&mut a.values));
+
+    let mut b = Conventional::new((nodes, elements));
+    for (i, j, &value) in distribution.iter() {
+        b.values[j * nodes + i] += value;
+    }
+    try!(factorization.solve_many(&mut b.values));
+
+    Ok((a, b))
+}
+
+/// Append a real matrix stored in the column-major order to a MAT-file of
+/// version 4 in the little-endian format.
+fn variable(buffer: &mut Vec<u8>, name: &str, (rows, columns): (usize, usize), values: &[f64]) {
+    let header = [0, rows as u32, columns as u32, 0, name.len() as u32 + 1];
+    for &value in &header {
+        for k in 0..4 {
+            buffer.push((value >> (8 * k)) as u8);
+        }
+    }
+    buffer.extend(name.as_bytes());
+    buffer.push(0);
+    for &value in values {
+        let value = value.to_bits();
+        for k in 0..8 {
+            buffer.push((value >> (8 * k)) as u8);
+        }
+    }
+}
@@ -1,29 +1,87 @@
 use ffi;
-use matrix::format::{Compressed, Diagonal};
-use std::mem;
+use matrix::format::{Compressed, Conventional, Diagonal};
+use std::cell::{Cell, Ref, RefCell, RefMut};
 use std::path::Path;
 
-use analysis:: Analysis;
+use analysis::{self, Analysis, AnalysisType};
+use builder::StackBuilder;
+use conductance;
+use exponential::{self, Exponential};
+use factorization::Factorization;
+use output::Output;
+use solver::{Iterative, Solver};
 use stack::{self, Stack};
-use {Raw, Result, power_grid, system_matrix, thermal_grid};
+use state_space::{self, Discretization, StateSpace};
+use system_matrix::{self, SystemMatrix};
+use thermal_grid::{self, ThermalGrid};
+use {Raw, Result, power_grid};
 
 /// A system.
+///
+/// The thermal grid and the system matrix are built on the first request and
+/// reused afterwards. The system matrix is rebuilt when the type of analysis or
+/// the step time changes, and an iterative solver is prepared anew when the
+/// solver changes as well. Everything is rebuilt when the stack is replaced.
+///
+/// A system can be sent to another thread, but it cannot be shared between
+/// threads, that is, `System` is `Send` but not `Sync`: several methods taking
+/// `&self` hand the structures of 3D-ICE to routines that are not guaranteed
+/// to leave them untouched.
 pub struct System {
     /// The stack.
     pub stack: Stack,
     /// The analysis.
     pub analysis: Analysis,
+    /// The output.
+    pub output: Output,
+    /// The solver used for steady-state and transient solves.
+    pub solver: Solver,
+
+    cache: Cache,
+}
+
+/// The structures that are expensive to build and are therefore kept between
+/// calls. The system matrices and iterative solvers are indexed by the type of
+/// analysis and, in the case of transient analysis, the step time that they
+/// have been built for. Since the stack of a system can be replaced, the cache
+/// also records the identifier of the stack that it has been built for.
+#[derive(Default)]
+struct Cache {
+    stack: Cell<usize>,
+    grid: RefCell<Option<ThermalGrid>>,
+    matrices: RefCell<Vec<((AnalysisType, f64), SystemMatrix, bool)>>,
+    solvers: RefCell<Vec<((AnalysisType, f64), Solver, Iterative)>>,
 }
 
 impl System {
     /// Create a system given a stack description.
+    ///
+    /// The function can be called from several threads at the same time. Since
+    /// the parser of 3D-ICE relies on global state, the parsing itself is
+    /// serialized by an internal lock.
     pub fn new<T: AsRef<Path>>(path: T) -> Result<System> {
         unsafe {
-            let (stack, analysis, _) = try!(stack::new(path.as_ref()));
-            Ok(System { stack: stack, analysis: analysis })
+            let (stack, analysis, output) = try!(stack::new(path.as_ref()));
+            Ok(System {
+                stack: stack,
+                analysis: analysis,
+                output: output,
+                solver: Solver::Direct,
+                cache: Cache::default(),
+            })
         }
     }
 
+    /// Extract the vector of the heat flowing in from the ambient.
+    ///
+    /// The vector accounts for the heat sink and for the coolant entering the
+    /// channels. Together with the conductance and distribution matrices, it
+    /// constitutes the steady-state system `G T = B P + b`.
+    #[inline]
+    pub fn boundary(&self) -> Result<Vec<f64>> {
+        unsafe { extract_boundary(self) }
+    }
+
     /// Extract the thermal capacitance matrix.
     #[inline]
     pub fn capacitance(&self) -> Result<Diagonal<f64>> {
@@ -41,10 +99,72 @@ impl System {
     pub fn distribution(&self) -> Result<Compressed<f64>> {
         unsafe { extract_distribution(self) }
     }
+
+    /// Prepare the exact solution of the transient problem.
+    ///
+    /// The cached factors of the solution correspond to the step time of the
+    /// analysis. If the analysis is not transient, the step time should be set
+    /// before stepping. See `Exponential` for the details.
+    #[inline]
+    pub fn exponential(&self) -> Result<Exponential> {
+        unsafe { exponential::new(self) }
+    }
+
+    /// Factorize the thermal conductance matrix.
+    ///
+    /// The matrix is the one returned by `conductance`, that is, `G` in the
+    /// steady state and `C/dt + G` in the transient analysis.
+    #[inline]
+    pub fn factorize(&self) -> Result<Factorization> {
+        Factorization::new(&try!(self.conductance()))
+    }
+
+    /// Extract the power values given in the floorplans.
+    ///
+    /// The rows of the resulting matrix correspond to the processing elements
+    /// and columns to the time slots. The rows are ordered as the columns of
+    /// the distribution matrix.
+    #[inline]
+    pub fn power_trace(&self) -> Result<Conventional<f64>> {
+        unsafe { extract_power_trace(self) }
+    }
+
+    /// Discretize the transient problem with a sampling interval.
+    ///
+    /// See `StateSpace` for the details.
+    #[inline]
+    pub fn state_space(&self, interval: f64, discretization: Discretization)
+                       -> Result<StateSpace> {
+        unsafe { state_space::new(self, interval, discretization) }
+    }
+
+    /// Compute the temperature in the steady state.
+    ///
+    /// The power vector should have one entry per processing element, and the
+    /// elements should be ordered as the columns of the distribution matrix.
+    /// The result has one entry per thermal node.
+    #[inline]
+    pub fn steady_state(&self, power: &[f64]) -> Result<Vec<f64>> {
+        unsafe { compute_steady_state(self, power) }
+    }
+
+    /// Write the system into a stack-description file.
+    ///
+    /// The floorplans of the dies are written as described in
+    /// `StackBuilder::write`.
+    #[inline]
+    pub fn write<T: AsRef<Path>>(&self, path: T) -> Result<()> {
+        StackBuilder::from(self).write(path)
+    }
+}
+
+unsafe fn extract_boundary(system: &System) -> Result<Vec<f64>> {
+    let grid = try!(grid(system));
+    try!(power_grid::new(&system.stack)).boundary(&grid, &system.stack)
 }
 
 unsafe fn extract_capacitance(system: &System) -> Result<Diagonal<f64>> {
-    let grid = try!(thermal_grid::new(&system.stack));
+    let grid = try!(grid(system));
     let grid = grid.raw();
 
     let stack = system.stack.raw();
@@ -67,21 +187,145 @@ unsafe fn extract_capacitance(system: &System) -> Result<Diagonal<f64>> {
 }
 
 unsafe fn extract_conductance(system: &System) -> Result<Compressed<f64>> {
-    use superlu::{FromSuperMatrix, SuperMatrix};
+    conductance::assemble(&system.stack, &system.analysis, &*try!(grid(system)))
+}
+
+unsafe fn extract_distribution(system: &System) -> Result<Compressed<f64>> {
+    try!(power_grid::new(&system.stack)).distribution()
+}
+
+unsafe fn extract_power_trace(system: &System) -> Result<Conventional<f64>> {
+    try!(power_grid::new(&system.stack)).power()
+}
 
-    let grid = try!(thermal_grid::new(&system.stack));
-    let matrix = try!(system_matrix::new(&system.stack, &system.analysis, &grid));
+unsafe fn compute_steady_state(system: &System, power: &[f64]) -> Result<Vec<f64>> {
+    use matrix::Size;
 
-    let matrix = SuperMatrix::from_raw(matrix.raw().SLUMatrix_A);
-    let result = Compressed::from_super_matrix(&matrix);
-    mem::forget(matrix);
+    let distribution = try!(extract_distribution(system));
+    if power.len() != distribution.columns() {
+        raise!("the power vector has a wrong size");
+    }
+
+    let mut analysis = try!(analysis::new());
+    analysis.set_steady();
 
-    match result {
-        Some(matrix) => Ok(matrix),
-        _ => raise!("failed to convert the system matrix"),
+    let mut source = try!(extract_boundary(system));
+    for (i, j, &value) in distribution.iter() {
+        source[i] += value * power[j];
     }
+
+    let mut temperature = vec![system.analysis.initial_temperature(); source.len()];
+    try!(solve(system, &analysis, &source, &mut temperature));
+
+    Ok(temperature)
 }
 
-unsafe fn extract_distribution(system: &System) -> Result<Compressed<f64>> {
-    try!(power_grid::new(&system.stack)).distribution()
+/// Return the thermal grid of a system building it if needed.
+pub unsafe fn grid<'l>(system: &'l System) -> Result<Ref<'l, ThermalGrid>> {
+    validate(system);
+    let cache = &system.cache.grid;
+    if cache.borrow().is_none() {
+        let grid = try!(thermal_grid::new(&system.stack));
+        *cache.borrow_mut() = Some(grid);
+    }
+    Ok(Ref::map(cache.borrow(), |grid| grid.as_ref().unwrap()))
+}
+
+/// Call a function with the system matrix of a system corresponding to an
+/// analysis building and factorizing the matrix if needed.
+///
+/// Building a matrix evicts the one built for another step time of the same
+/// type of analysis.
+pub unsafe fn with_matrix<F, T>(system: &System, analysis: &Analysis, factorize: bool,
+                                function: F) -> Result<T>
+    where F: FnOnce(&mut SystemMatrix) -> Result<T>
+{
+    validate(system);
+    let key = key(analysis);
+    let mut matrices = system.cache.matrices.borrow_mut();
+    let position = match matrices.iter().position(|&(other, _, _)| other == key) {
+        Some(position) => position,
+        _ => {
+            let matrix = try!(system_matrix::new(&system.stack, analysis, &*try!(grid(system))));
+            matrices.retain(|&((kind, _), _, _)| kind != key.0);
+            matrices.push((key, matrix, false));
+            matrices.len() - 1
+        },
+    };
+    let (_, ref mut matrix, ref mut factorized) = matrices[position];
+    if factorize && !*factorized {
+        try!(matrix.factorize());
+        *factorized = true;
+    }
+    function(matrix)
+}
+
+/// Prepare the solver of a system for an analysis so that subsequent calls to
+/// `solve` are fast.
+pub unsafe fn prepare(system: &System, analysis: &Analysis) -> Result<()> {
+    match system.solver {
+        Solver::Direct => with_matrix(system, analysis, true, |_| Ok(())),
+        _ => iterative(system, analysis).map(|_| ()),
+    }
+}
+
+/// Solve the system corresponding to an analysis using the solver of the
+/// system. The solution vector holds the initial guess on entry, which only
+/// iterative solvers make use of.
+pub unsafe fn solve(system: &System, analysis: &Analysis, b: &[f64], x: &mut [f64])
+                    -> Result<()> {
+    if b.len() != x.len() {
+        raise!("the right-hand side has a wrong size");
+    }
+    match system.solver {
+        Solver::Direct => {
+            x.copy_from_slice(b);
+            with_matrix(system, analysis, true, |matrix| matrix.solve(x))
+        },
+        _ => try!(iterative(system, analysis)).solve(b, x),
+    }
+}
+
+unsafe fn iterative<'l>(system: &'l System, analysis: &Analysis)
+                        -> Result<RefMut<'l, Iterative>> {
+    validate(system);
+    let key = key(analysis);
+    let mut solvers = system.cache.solvers.borrow_mut();
+    let position = match solvers.iter().position(|&(other, solver, _)| {
+        other == key && solver == system.solver
+    }) {
+        Some(position) => position,
+        _ => {
+            let matrix = try!(conductance::assemble(&system.stack, analysis,
+                                                    &*try!(grid(system))));
+            let dimensions = system.stack.raw().Dimensions;
+            let grid = (ffi::get_number_of_layers(dimensions) as usize,
+                        ffi::get_number_of_rows(dimensions) as usize,
+                        ffi::get_number_of_columns(dimensions) as usize);
+            let solver = try!(Iterative::new(system.solver, &matrix, grid));
+            solvers.retain(|&((kind, _), _, _)| kind != key.0);
+            solvers.push((key, system.solver, solver));
+            solvers.len() - 1
+        },
+    };
+    Ok(RefMut::map(solvers, |solvers| &mut solvers[position].2))
+}
+
+/// Empty the cache of a system if it has been built for another stack.
+fn validate(system: &System) {
+    let cache = &system.cache;
+    let id = stack::id(&system.stack);
+    if cache.stack.get() != id {
+        *cache.grid.borrow_mut() = None;
+        cache.matrices.borrow_mut().clear();
+        cache.solvers.borrow_mut().clear();
+        cache.stack.set(id);
+    }
+}
+
+fn key(analysis: &Analysis) -> (AnalysisType, f64) {
+    match analysis.kind() {
+        AnalysisType::Transient => (AnalysisType::Transient, analysis.step_time()),
+        kind => (kind, 0.0),
+    }
 }
@@ -1,5 +1,6 @@
 use ffi;
 use std::mem;
+use superlu_sys as slu;
 
 use analysis::Analysis;
 use stack::Stack;
@@ -11,6 +12,35 @@ pub struct SystemMatrix {
     raw: ffi::SystemMatrix_t,
 }
 
+impl SystemMatrix {
+    /// Perform the LU factorization.
+    pub fn factorize(&mut self) -> Result<()> {
+        unsafe {
+            success!(do_factorization(&mut self.raw), "factorize the system matrix");
+        }
+        Ok(())
+    }
+
+    /// Solve the system for a right-hand side, which gets overwritten by the
+    /// solution. The matrix should be factorized beforehand.
+    pub fn solve(&mut self, b: &mut [f64]) -> Result<()> {
+        let size = self.raw.Size as usize;
+        if b.len() != size {
+            raise!("the right-hand side has a wrong size");
+        }
+        unsafe {
+            let mut matrix = mem::uninitialized();
+            slu::dCreate_Dense_Matrix(&mut matrix, size as _, 1, b.as_mut_ptr(), size as _,
+                                      slu::Stype_t::SLU_DN, slu::Dtype_t::SLU_D,
+                                      slu::Mtype_t::SLU_GE);
+            let result = solve_sparse_linear_system(&mut self.raw, &mut matrix);
+            slu::Destroy_SuperMatrix_Store(&mut matrix);
+            success!(result, "solve the system");
+        }
+        Ok(())
+    }
+}
+
 impl Drop for SystemMatrix {
     fn drop(&mut self) {
         unsafe { ffi::system_matrix_destroy(&mut self.raw) };
@@ -19,6 +49,10 @@ impl Drop for SystemMatrix {
 
 implement_raw!(SystemMatrix, ffi::SystemMatrix_t);
 
+// The matrix and its factors allocated by 3D-ICE and SuperLU are owned
+// exclusively by the system matrix.
+unsafe impl Send for SystemMatrix {}
+
 pub unsafe fn new(stack: &Stack, analysis: &Analysis, grid: &ThermalGrid) -> Result<SystemMatrix> {
     let mut raw = mem::uninitialized();
     ffi::system_matrix_init(&mut raw);
@@ -27,9 +61,17 @@ pub unsafe fn new(stack: &Stack, analysis: &Analysis, grid: &ThermalGrid) -> Res
     let cells = ffi::get_number_of_cells(stack.Dimensions);
     let connections = ffi::get_number_of_connections(stack.Dimensions);
 
-    success!(ffi::system_matrix_build(&mut raw, cells, connections), "build the system matrix");
+    success!(ffi::system_matrix_build(&mut raw, cells, connections), Build,
+             object: "system matrix");
     ffi::fill_system_matrix(&mut raw, grid.raw() as *const _ as *mut _,
                             analysis.raw() as *const _ as *mut _, stack.Dimensions);
 
     Ok(SystemMatrix { raw: raw })
 }
+
+// The routines are a part of 3D-ICE but are not exposed by threed-ice-sys.
+extern "C" {
+    fn do_factorization(sysmatrix: *mut ffi::SystemMatrix_t) -> ffi::Error_t;
+    fn solve_sparse_linear_system(sysmatrix: *mut ffi::SystemMatrix_t,
+                                  b: *mut slu::SuperMatrix) -> ffi::Error_t;
+}
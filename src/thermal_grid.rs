@@ -1,33 +1,124 @@
 use ffi;
-use std::marker::PhantomData;
 use std::mem;
 
 use stack::Stack;
 use {Raw, Result};
 
+macro_rules! conductance(
+    ($grid:expr, $stack:expr, $routine:ident, $layer:expr, $row:expr, $column:expr) => (unsafe {
+        $routine(&$grid.raw as *const _ as *mut _, $stack.raw().Dimensions,
+                 $layer as _, $row as _, $column as _)
+    });
+);
+
 /// A thermal grid.
-pub struct ThermalGrid<'l> {
+///
+/// The grid refers to the channel and heat sink of the stack that it has been
+/// built for, and the stack should outlive it.
+pub struct ThermalGrid {
     raw: ffi::ThermalGrid_t,
-    phantom: PhantomData<&'l ffi::ThermalGrid_t>,
 }
 
-impl<'l> Drop for ThermalGrid<'l> {
+impl ThermalGrid {
+    /// Return the type of a layer.
+    #[inline]
+    pub fn profile(&self, layer: usize) -> ffi::StackLayerType_t {
+        unsafe { *self.raw.LayersProfile.offset(layer as isize) }
+    }
+
+    /// Return the thermal capacity of a cell.
+    #[inline]
+    pub fn capacity(&self, stack: &Stack, layer: usize, row: usize, column: usize) -> f64 {
+        unsafe {
+            ffi::get_capacity(&self.raw as *const _ as *mut _, stack.raw().Dimensions,
+                              layer as _, row as _, column as _)
+        }
+    }
+
+    /// Return the conductance between a cell and its top neighbor.
+    #[inline]
+    pub fn conductance_top(&self, stack: &Stack, layer: usize, row: usize, column: usize) -> f64 {
+        conductance!(self, stack, get_conductance_top, layer, row, column)
+    }
+
+    /// Return the conductance between a cell and its bottom neighbor.
+    #[inline]
+    pub fn conductance_bottom(&self, stack: &Stack, layer: usize, row: usize,
+                              column: usize) -> f64 {
+        conductance!(self, stack, get_conductance_bottom, layer, row, column)
+    }
+
+    /// Return the conductance between a cell and its north neighbor.
+    #[inline]
+    pub fn conductance_north(&self, stack: &Stack, layer: usize, row: usize,
+                             column: usize) -> f64 {
+        conductance!(self, stack, get_conductance_north, layer, row, column)
+    }
+
+    /// Return the conductance between a cell and its south neighbor.
+    #[inline]
+    pub fn conductance_south(&self, stack: &Stack, layer: usize, row: usize,
+                             column: usize) -> f64 {
+        conductance!(self, stack, get_conductance_south, layer, row, column)
+    }
+
+    /// Return the conductance between a cell and its east neighbor.
+    #[inline]
+    pub fn conductance_east(&self, stack: &Stack, layer: usize, row: usize,
+                            column: usize) -> f64 {
+        conductance!(self, stack, get_conductance_east, layer, row, column)
+    }
+
+    /// Return the conductance between a cell and its west neighbor.
+    #[inline]
+    pub fn conductance_west(&self, stack: &Stack, layer: usize, row: usize,
+                            column: usize) -> f64 {
+        conductance!(self, stack, get_conductance_west, layer, row, column)
+    }
+}
+
+impl Drop for ThermalGrid {
     fn drop(&mut self) {
         unsafe { ffi::thermal_grid_destroy(&mut self.raw) };
     }
 }
 
-implement_raw!(ThermalGrid, ffi::ThermalGrid_t, l);
+implement_raw!(ThermalGrid, ffi::ThermalGrid_t);
+
+// The profiles allocated by 3D-ICE are owned exclusively by the grid.
+unsafe impl Send for ThermalGrid {}
 
-pub unsafe fn new<'l>(stack: &'l Stack) -> Result<ThermalGrid<'l>> {
+pub unsafe fn new(stack: &Stack) -> Result<ThermalGrid> {
     let mut raw = mem::uninitialized();
     ffi::thermal_grid_init(&mut raw);
 
     let stack = stack.raw();
     let layers = ffi::get_number_of_layers(stack.Dimensions);
 
-    success!(ffi::thermal_grid_build(&mut raw, layers), "build the thermal grid");
+    success!(ffi::thermal_grid_build(&mut raw, layers), Build, object: "thermal grid");
     ffi::fill_thermal_grid(&mut raw, &stack.StackElements as *const _ as *mut _, stack.Dimensions);
 
-    Ok(ThermalGrid { raw: raw, phantom: PhantomData })
+    Ok(ThermalGrid { raw: raw })
+}
+
+// The routines are a part of 3D-ICE but are not exposed by threed-ice-sys.
+extern "C" {
+    fn get_conductance_top(tgrid: *mut ffi::ThermalGrid_t, dimensions: *mut ffi::Dimensions_t,
+                           layer_index: ffi::CellIndex_t, row_index: ffi::CellIndex_t,
+                           column_index: ffi::CellIndex_t) -> f64;
+    fn get_conductance_bottom(tgrid: *mut ffi::ThermalGrid_t, dimensions: *mut ffi::Dimensions_t,
+                              layer_index: ffi::CellIndex_t, row_index: ffi::CellIndex_t,
+                              column_index: ffi::CellIndex_t) -> f64;
+    fn get_conductance_north(tgrid: *mut ffi::ThermalGrid_t, dimensions: *mut ffi::Dimensions_t,
+                             layer_index: ffi::CellIndex_t, row_index: ffi::CellIndex_t,
+                             column_index: ffi::CellIndex_t) -> f64;
+    fn get_conductance_south(tgrid: *mut ffi::ThermalGrid_t, dimensions: *mut ffi::Dimensions_t,
+                             layer_index: ffi::CellIndex_t, row_index: ffi::CellIndex_t,
+                             column_index: ffi::CellIndex_t) -> f64;
+    fn get_conductance_east(tgrid: *mut ffi::ThermalGrid_t, dimensions: *mut ffi::Dimensions_t,
+                            layer_index: ffi::CellIndex_t, row_index: ffi::CellIndex_t,
+                            column_index: ffi::CellIndex_t) -> f64;
+    fn get_conductance_west(tgrid: *mut ffi::ThermalGrid_t, dimensions: *mut ffi::Dimensions_t,
+                            layer_index: ffi::CellIndex_t, row_index: ffi::CellIndex_t,
+                            column_index: ffi::CellIndex_t) -> f64;
 }
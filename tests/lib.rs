@@ -4,20 +4,267 @@ extern crate matrix;
 extern crate temporary;
 extern crate threed_ice;
 
+use std::fs::File;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use temporary::Directory;
 
-use threed_ice::{AnalysisType, StackElement, System};
+use threed_ice::{AnalysisType, ChannelModel, Discretization, Floorplan, FloorplanElement};
+use threed_ice::{HeatSink, HeatSinkModel, HeatSinkPart, Layer, Material, Node};
+use threed_ice::{OutputInstant, OutputInstruction, OutputQuantity, OutputType, Rectangle};
+use threed_ice::{Simulator, StackBuilder, StackElement, StateSpace, System};
 
 macro_rules! ok(
     ($result:expr) => ($result.unwrap());
 );
 
+#[test]
+fn simulator_output() {
+    use std::fs;
+
+    setup(None, |path| {
+        let directory = ok!(path.parent());
+        let file = |name: &str| format!("\"{}\"", directory.join(name).display());
+        let mut description = ok!(fs::read_to_string(path)).replace("\"T.txt\"", &file("T.txt"))
+                                                            .replace("\"P.txt\"", &file("P.txt"));
+        description.push_str(&format!("   Pmap(DIE1, {}, final);\n", file("P_final.txt")));
+        let path = directory.join("output.stk");
+        ok!(ok!(File::create(&path)).write_all(description.as_bytes()));
+
+        let system = ok!(System::new(&path));
+        assert_eq!(system.output.instructions.len(), 3);
+        let mut simulator = ok!(Simulator::new(&system));
+        ok!(simulator.enable_output());
+        let temperature = ok!(simulator.run(&[10.0, 20.0, 30.0, 40.0, 1.0, 2.0, 3.0, 4.0]));
+        ok!(simulator.finish());
+
+        let read = |name: &str| {
+            let content = ok!(fs::read_to_string(directory.join(name)));
+            assert!(content.starts_with("%"));
+            content.split("\n\n").map(|block| {
+                block.lines().filter(|line| !line.starts_with("%"))
+                     .flat_map(|line| line.split_whitespace())
+                     .map(|value| ok!(value.parse::<f64>())).collect::<Vec<_>>()
+            }).filter(|block| !block.is_empty()).collect::<Vec<_>>()
+        };
+
+        let maps = read("T.txt");
+        assert_eq!(maps.len(), 2);
+        assert::close(&maps[0], &temperature[..4], 1e-3 * 320.0);
+        assert::close(&maps[1], &temperature[16..20], 1e-3 * 320.0);
+
+        let sum = |map: &Vec<f64>| map.iter().fold(0.0, |sum, &value| sum + value);
+        let maps = read("P.txt");
+        assert_eq!(maps.len(), 2);
+        assert::close(&[sum(&maps[0]), sum(&maps[1])], &[100.0, 10.0], 1e-2);
+        let maps = read("P_final.txt");
+        assert_eq!(maps.len(), 1);
+        assert::close(&[sum(&maps[0])], &[10.0], 1e-2);
+    });
+}
+
+#[test]
+fn simulator_run() {
+    setup(None, |path| {
+        let system = ok!(System::new(path));
+        let mut simulator = ok!(Simulator::new(&system));
+        assert_eq!(simulator.elements(), 4);
+        assert_eq!(simulator.nodes(), 4 * 2 * 2);
+
+        let temperature = ok!(simulator.run(&vec![0.0; 4 * 3]));
+        assert::close(&temperature, &vec![318.15; 4 * 2 * 2 * 3], 1e-8);
+        assert::close(&[simulator.time()], &[0.003], 1e-12);
+
+        simulator.reset();
+        assert_eq!(simulator.time(), 0.0);
+
+        let power = vec![10.0, 20.0, 30.0, 40.0, 10.0, 20.0, 30.0, 40.0];
+        let temperature = ok!(simulator.run(&power));
+        assert_eq!(temperature.len(), 4 * 2 * 2 * 2);
+        assert!((0..16).all(|i| temperature[i] > 318.15));
+        assert!((0..16).all(|i| temperature[16 + i] > temperature[i]));
+
+        assert!(simulator.run(&[0.0]).is_err());
+    });
+}
+
 #[test]
 fn system_analysis() {
     setup(None, |path| {
         let system = ok!(System::new(path));
-        assert_eq!(system.analysis.kind(), AnalysisType::Transient);
+        let analysis = &system.analysis;
+        assert_eq!(analysis.kind(), AnalysisType::Transient);
+        assert_eq!(analysis.step_time(), 0.001);
+        assert_eq!(analysis.slot_time(), 0.001);
+        assert_eq!(analysis.slot_length(), 1);
+        assert_eq!(analysis.initial_temperature(), 318.15);
+    });
+}
+
+#[test]
+fn system_analysis_override() {
+    setup(None, |path| {
+        let mut system = ok!(System::new(path));
+
+        assert!(system.analysis.set_transient(0.0, 0.001).is_err());
+        assert!(system.analysis.set_transient(0.002, 0.001).is_err());
+        assert!(system.analysis.set_transient(0.002, 0.005).is_err());
+
+        ok!(system.analysis.set_transient(0.001, 0.005));
+        system.analysis.set_initial_temperature(300.0);
+        assert_eq!(system.analysis.slot_length(), 5);
+        assert_eq!(system.analysis.initial_temperature(), 300.0);
+
+        let mut simulator = ok!(Simulator::new(&system));
+        assert::close(&simulator.temperature(), &vec![300.0; 4 * 2 * 2], 0.0);
+        ok!(simulator.slot(&[0.0, 0.0, 0.0, 0.0]));
+        assert::close(&[simulator.time()], &[0.005], 1e-12);
+
+        system.analysis.set_steady();
+        assert_eq!(system.analysis.kind(), AnalysisType::Steady);
+        assert!(Simulator::new(&system).is_err());
+    });
+}
+
+#[test]
+fn system_boundary() {
+    setup(None, |path| {
+        let mut system = ok!(System::new(path));
+        let boundary = ok!(system.boundary());
+        assert_eq!(boundary.len(), 4 * 2 * 2);
+        assert!(boundary[..12].iter().all(|&value| value == 0.0));
+        assert!(boundary[12..].iter().all(|&value| value > 0.0));
+
+        system.analysis.set_steady();
+        let conductance = ok!(system.conductance());
+        let mut product = vec![0.0; 4 * 2 * 2];
+        for (i, _, &value) in conductance.iter() {
+            product[i] += value * 318.15;
+        }
+        assert::close(&product, &boundary, 1e-6);
+    });
+
+    let flow = 4.172638e-12 * 48.0 * 1e12 / 60.0;
+    let inlets = [("channel_4rm", 11, 5, flow / (2.0 * 5.0)),
+                  ("channel_2rm", 5, 5, flow * 0.5 * 100.0 / (2.0 * 5.0 * 50.0))];
+    for &(name, columns, channels, convective) in &inlets {
+        setup(Some(name), |path| {
+            let system = ok!(System::new(path));
+            let dimensions = &system.stack.dimensions;
+            assert_eq!((dimensions.rows(), dimensions.columns()), (2, columns));
+
+            let boundary = ok!(system.boundary());
+            let nodes = (0..boundary.len()).filter(|&i| boundary[i] != 0.0)
+                                           .collect::<Vec<_>>();
+            assert_eq!(nodes.len(), channels);
+            for &i in &nodes {
+                let (layer, row, column) = (i / (2 * columns), i / columns % 2, i % columns);
+                assert_eq!((layer, row), (nodes[0] / (2 * columns), 0));
+                assert!(columns == channels || column % 2 == 1);
+                assert::close(&[boundary[i]], &[2.0 * convective * 300.0], 1e-10 * boundary[i]);
+            }
+        });
+    }
+}
+
+#[test]
+fn system_builder() {
+    let material = |id: &str, conductivity, capacity| {
+        Material { id: id.to_string(), thermal_conductivity: conductivity,
+                   volumetric_heat_capacity: capacity }
+    };
+    let silicon = material("SILICON", 100e-6, 1.75e-12);
+    let interface = material("INTERFACE_MATERIAL", 4.0e-6, 4.0e-12);
+    let sink = material("HEAT_SINK", 400e-6, 3.55e-12);
+    let spreader = material("HEAT_SPREADER", 400e-6, 3.55e-12);
+
+    let layer = |height, material: &Material| {
+        Layer { id: None, height: height, material: material.clone() }
+    };
+    let part = |height, area, material: &Material| {
+        HeatSinkPart { height: height, area: area, material: material.clone() }
+    };
+
+    let rising = (0..20).map(|i| 10.0 + 5.0 * (i / 4) as f64).collect::<Vec<_>>();
+    let falling = rising.iter().rev().cloned().collect::<Vec<_>>();
+    let element = |id: &str, x, y, power: &Vec<f64>| {
+        FloorplanElement::new(id, vec![Rectangle::new(x, y, 2000.0, 2000.0)], power.clone())
+    };
+    let floorplan = Floorplan { elements: vec![
+        element("Core0", 0.0, 0.0, &rising), element("Core1", 2000.0, 0.0, &falling),
+        element("Core2", 0.0, 2000.0, &rising), element("Core3", 2000.0, 2000.0, &falling),
+    ]};
+
+    let builder = StackBuilder::new()
+        .material(silicon.clone())
+        .heat_sink(HeatSink {
+            model: HeatSinkModel::Traditional,
+            heat_transfer_coefficient: 1.0e-7,
+            ambient_temperature: 318.15,
+            sink: Some(part(0.0069e+6, 0.0036e+12, &sink)),
+            spreader: Some(part(0.001e+6, 0.0009e+12, &spreader)),
+        })
+        .die("DIE", vec![layer(0.00015e+6, &silicon), layer(0.00002e+6, &interface)], 0)
+        .dimensions(0.004e6, 0.004e6, 0.002e6, 0.002e6)
+        .stack_die("DIE1", "DIE", floorplan)
+        .transient(0.001, 0.001, 318.15);
+
+    let built = ok!(builder.build());
+    setup(None, |path| {
+        let system = ok!(System::new(path));
+        assert_eq!(built.stack.materials, system.stack.materials);
+        assert_eq!(built.stack.elements, system.stack.elements);
+        assert_eq!(ok!(built.capacitance()).values, ok!(system.capacitance()).values);
+        assert_eq!(ok!(built.conductance()), ok!(system.conductance()));
+        assert_eq!(ok!(built.distribution()), ok!(system.distribution()));
+        assert_eq!(ok!(built.power_trace()), ok!(system.power_trace()));
+    });
+
+    assert!(builder.clone().material(material("SILICON", 150e-6, 1.75e-12)).build().is_err());
+    assert!(builder.clone().material(material("X; Y", 150e-6, 1.75e-12)).build().is_err());
+    assert!(builder.clone().stack_layer("LAYER", "X; Y").build().is_err());
+    assert!(StackBuilder::new().build().is_err());
+}
+
+#[test]
+fn system_cache() {
+    use std::mem;
+
+    setup(None, |path| {
+        let mut system = ok!(System::new(path));
+        let power = vec![10.0, 20.0, 30.0, 40.0];
+
+        let transient = ok!(system.conductance());
+        assert_eq!(ok!(system.conductance()), transient);
+        let temperature = ok!(system.steady_state(&power));
+        assert_eq!(ok!(system.steady_state(&power)), temperature);
+        assert_eq!(ok!(system.conductance()), transient);
+
+        ok!(system.analysis.set_transient(0.002, 0.002));
+        assert!(ok!(system.conductance()) != transient);
+        ok!(system.analysis.set_transient(0.001, 0.001));
+        assert_eq!(ok!(system.conductance()), transient);
+        system.analysis.set_steady();
+        assert!(ok!(system.conductance()) != transient);
+        ok!(system.analysis.set_transient(0.001, 0.001));
+
+        let fresh = ok!(System::new(path));
+        let mut simulators = (ok!(Simulator::new(&system)), ok!(Simulator::new(&fresh)));
+        for _ in 0..3 {
+            ok!(simulators.0.step(&power));
+            ok!(simulators.1.step(&power));
+        }
+        assert_eq!(simulators.0.temperature(), simulators.1.temperature());
+        assert_eq!(ok!(system.steady_state(&power)), temperature);
+        drop(simulators);
+
+        let mut other = None;
+        setup(Some("channel_4rm"), |path| other = Some(ok!(System::new(path))));
+        let mut other = ok!(other);
+        let expected = ok!(other.steady_state(&power));
+        mem::swap(&mut system.stack, &mut other.stack);
+        assert_eq!(ok!(system.steady_state(&power)), expected);
+        assert_eq!(ok!(other.steady_state(&power)), temperature);
     });
 }
 
@@ -33,6 +280,63 @@ fn system_capacitance() {
     });
 }
 
+#[test]
+fn system_channel() {
+    let fixtures = [
+        ("channel_4rm", ChannelModel::Microchannel4RM, (2.7132e-8, 4.7132e-8, 5.7132e-8)),
+        ("channel_2rm", ChannelModel::Microchannel2RM, (0.0, 4.7132e-8, 5.7132e-8)),
+    ];
+    for &(name, model, coefficients) in &fixtures {
+        setup(Some(name), |path| {
+            let system = ok!(System::new(path));
+            let channels = system.stack.elements.iter().filter_map(|element| match element {
+                &StackElement::Channel(ref channel) => Some(channel),
+                _ => None,
+            }).collect::<Vec<_>>();
+            assert_eq!(channels.len(), 1);
+
+            let channel = channels[0];
+            assert_eq!(channel.model, model);
+            assert_eq!(channel.channels, 5);
+            assert_eq!(channel.wall_material, Material {
+                id: "SILICON".to_string(), thermal_conductivity: 1.30e-4,
+                volumetric_heat_capacity: 1.63566e-12,
+            });
+            assert::close(&[channel.height, channel.length, channel.pitch, channel.porosity],
+                          &[100.0, 50.0, 100.0, 0.5], 1e-12);
+
+            let coolant = &channel.coolant;
+            assert::close(&[coolant.side_heat_transfer_coefficient,
+                            coolant.top_heat_transfer_coefficient,
+                            coolant.bottom_heat_transfer_coefficient],
+                          &[coefficients.0, coefficients.1, coefficients.2], 1e-20);
+            assert::close(&[coolant.volumetric_heat_capacity], &[4.172638e-12], 1e-24);
+            assert::close(&[coolant.flow_rate], &[48.0 * 1e12 / 60.0], 1e-3);
+            assert::close(&[coolant.temperature], &[300.0], 1e-12);
+        });
+    }
+}
+
+#[test]
+fn system_concurrency() {
+    use std::thread;
+
+    let threads = (0..8).map(|i| thread::spawn(move || {
+        let mut system = None;
+        setup(Some(if i % 2 == 0 { "default" } else { "double" }), |path| {
+            system = Some(ok!(System::new(path)));
+        });
+        ok!(system)
+    })).collect::<Vec<_>>();
+
+    let systems = threads.into_iter().map(|thread| ok!(thread.join())).collect::<Vec<_>>();
+    for (i, system) in systems.iter().enumerate() {
+        assert_eq!(system.stack.elements, systems[i % 2].stack.elements);
+        assert_eq!(ok!(system.conductance()), ok!(systems[i % 2].conductance()));
+    }
+    assert!(systems[0].stack.elements != systems[1].stack.elements);
+}
+
 #[test]
 fn system_conductance() {
     setup(None, |path| {
@@ -70,9 +374,60 @@ fn system_conductance() {
         assert_eq!(&conductance.offsets[..], &vec![
             0, 4, 8, 12, 16, 21, 26, 31, 36, 39, 42, 45, 48, 50, 52, 54, 56,
         ][..]);
+
+        let mut system = ok!(System::new(path));
+        let capacitance = ok!(system.capacitance());
+        system.analysis.set_steady();
+        let steady = ok!(system.conductance());
+        assert_eq!(&steady.indices, &conductance.indices);
+        assert_eq!(&steady.offsets, &conductance.offsets);
+        for (i, j, &value) in conductance.iter() {
+            let expected = if i == j { value - capacitance.values[i] / 0.001 } else { value };
+            assert::close(&[steady.get((i, j))], &[expected], 1e-10);
+        }
     });
 }
 
+#[test]
+fn system_conductance_channel() {
+    fn check(conductance: &matrix::format::Compressed<f64>, x: &[f64], b: &[f64]) {
+        let mut product = vec![0.0; x.len()];
+        let mut scale = vec![0.0; x.len()];
+        for (i, j, &value) in conductance.iter() {
+            product[i] += value * x[j];
+            scale[i] += (value * x[j]).abs();
+        }
+        for i in 0..x.len() {
+            assert::close(&[product[i]], &[b[i]], 1e-10 * scale[i]);
+        }
+    }
+
+    for &name in &["channel_4rm", "channel_2rm"] {
+        setup(Some(name), |path| {
+            let mut system = ok!(System::new(path));
+            let power = vec![1.0, 2.0, 3.0, 4.0];
+            let distribution = ok!(system.distribution());
+            let mut b = ok!(system.boundary());
+            for (i, j, &value) in distribution.iter() {
+                b[i] += value * power[j];
+            }
+
+            let mut simulator = ok!(Simulator::new(&system));
+            ok!(simulator.step(&power));
+            let capacitance = ok!(system.capacitance());
+            let transient = capacitance.values.iter().zip(&b).map(|(&capacitance, &b)| {
+                b + capacitance / 0.001 * 300.0
+            }).collect::<Vec<_>>();
+            check(&ok!(system.conductance()), simulator.temperature(), &transient);
+            drop(simulator);
+
+            let x = ok!(system.steady_state(&power));
+            system.analysis.set_steady();
+            check(&ok!(system.conductance()), &x, &b);
+        });
+    }
+}
+
 #[test]
 fn system_distribution() {
     use matrix::Size;
@@ -110,6 +465,428 @@ fn system_distribution() {
     });
 }
 
+#[test]
+fn system_error() {
+    use std::{fs, io};
+    use threed_ice::Error;
+
+    let path = Path::new("tests/fixtures/missing.stk");
+    match System::new(path) {
+        Err(Error::MissingFile(ref other)) => assert_eq!(other, path),
+        _ => unreachable!(),
+    }
+    let error: io::Error = System::new(path).err().unwrap().into();
+    assert_eq!(error.kind(), io::ErrorKind::NotFound);
+
+    setup(None, |path| {
+        ok!(fs::write(path, "material SILICON:\n   thermal conductivity 100e-6\n"));
+        match System::new(path) {
+            Err(Error::Parse { status, ref diagnostics, .. }) => {
+                assert!(status != 0);
+                assert_eq!(diagnostics.len(), 1);
+                assert_eq!(diagnostics[0].file.as_ref().map(|file| &**file), Some(path));
+                assert!(diagnostics[0].line.is_some());
+                assert!(diagnostics[0].message.contains("expected"));
+            },
+            _ => unreachable!(),
+        }
+    });
+
+    setup(None, |path| {
+        ok!(fs::write(path.with_file_name("004.flp"), "Core0:\n   position 0;\n"));
+        match System::new(path) {
+            Err(Error::Parse { ref diagnostics, .. }) => {
+                assert!(diagnostics.iter().any(|diagnostic| {
+                    diagnostic.file.as_ref().map_or(false, |file| file.ends_with("004.flp")) &&
+                        diagnostic.line == Some(2) && diagnostic.column == Some(14)
+                }));
+            },
+            _ => unreachable!(),
+        }
+    });
+}
+
+#[test]
+fn system_exponential() {
+    setup(None, |path| {
+        let mut system = ok!(System::new(path));
+        let mut exponential = ok!(system.exponential());
+        assert_eq!(exponential.elements(), 4);
+        assert_eq!(exponential.nodes(), 4 * 2 * 2);
+        assert_eq!(exponential.step_time(), Some(0.001));
+        assert!(exponential.values().iter().all(|&value| value > 0.0));
+
+        let mut temperature = vec![318.15; 4 * 2 * 2];
+        let result = ok!(exponential.run(&mut temperature, &vec![0.0; 4 * 3]));
+        assert::close(&result, &vec![318.15; 4 * 2 * 2 * 3], 1e-8);
+
+        let power = [10.0, 20.0, 30.0, 40.0];
+        let mut stepped = vec![318.15; 4 * 2 * 2];
+        ok!(exponential.step(&mut stepped, &power));
+        ok!(exponential.step(&mut stepped, &power));
+        let mut advanced = vec![318.15; 4 * 2 * 2];
+        ok!(exponential.advance(&mut advanced, &power, 0.002));
+        assert::close(&stepped, &advanced, 1e-8);
+
+        ok!(exponential.set_step_time(0.002));
+        let mut temperature = vec![318.15; 4 * 2 * 2];
+        assert::close(&ok!(exponential.run(&mut temperature, &power)), &advanced, 1e-8);
+
+        let mut temperature = vec![318.15; 4 * 2 * 2];
+        ok!(exponential.advance(&mut temperature, &power, 1e6));
+        assert::close(&temperature, &ok!(system.steady_state(&power)), 1e-6);
+
+        ok!(system.analysis.set_transient(1e-6, 1e-3));
+        let mut simulator = ok!(Simulator::new(&system));
+        ok!(simulator.slot(&power));
+        let mut temperature = vec![318.15; 4 * 2 * 2];
+        ok!(exponential.advance(&mut temperature, &power, 1e-3));
+        assert::close(simulator.temperature(), &temperature, 1e-3);
+
+        assert!(exponential.step(&mut [0.0], &power).is_err());
+        assert!(exponential.set_step_time(0.0).is_err());
+
+        system.analysis.set_steady();
+        let mut exponential = ok!(system.exponential());
+        assert_eq!(exponential.step_time(), None);
+        let mut temperature = vec![318.15; 4 * 2 * 2];
+        assert!(exponential.step(&mut temperature, &power).is_err());
+        assert!(exponential.run(&mut temperature, &power).is_err());
+        ok!(exponential.set_step_time(0.002));
+        assert::close(&ok!(exponential.run(&mut temperature, &power)), &advanced, 1e-8);
+    });
+}
+
+#[test]
+fn system_factorization() {
+    setup(None, |path| {
+        let mut system = ok!(System::new(path));
+        system.analysis.set_steady();
+
+        let boundary = ok!(system.boundary());
+        let distribution = ok!(system.distribution());
+        let factorization = ok!(system.factorize());
+        assert_eq!(factorization.size(), 4 * 2 * 2);
+
+        let powers = [[0.0, 0.0, 0.0, 0.0], [10.0, 20.0, 30.0, 40.0], [5.0, 0.0, 15.0, 1.0]];
+        let mut rhs = vec![];
+        for power in &powers {
+            let mut column = boundary.clone();
+            for (i, j, &value) in distribution.iter() {
+                column[i] += value * power[j];
+            }
+            rhs.extend(column);
+        }
+
+        let mut single = rhs[16..32].to_vec();
+        ok!(factorization.solve(&mut single));
+        assert::close(&single, &ok!(system.steady_state(&powers[1])), 1e-8);
+
+        ok!(factorization.solve_many(&mut rhs));
+        for (k, power) in powers.iter().enumerate() {
+            let expected = ok!(system.steady_state(power));
+            assert::close(&rhs[(16 * k)..(16 * (k + 1))], &expected, 1e-8);
+        }
+
+        assert!(factorization.solve(&mut [0.0]).is_err());
+        assert!(factorization.solve_many(&mut [0.0; 17]).is_err());
+    });
+}
+
+#[test]
+fn system_floorplan() {
+    setup(Some("double"), |path| {
+        let system = ok!(System::new(path));
+        let die = match &system.stack.elements[1] {
+            &StackElement::Die(ref die) => die,
+            _ => unreachable!(),
+        };
+        let elements = &die.floorplan.elements;
+
+        let rectangle = ok!(elements[3].bounding_box());
+        assert_eq!((rectangle.x, rectangle.y), (2000.0, 2000.0));
+        assert_eq!((rectangle.length, rectangle.width), (2000.0, 2000.0));
+        assert_eq!((rectangle.rows, rectangle.columns), (2..4, 2..4));
+
+        assert_eq!(elements[0].rectangles.len(), 1);
+        assert_eq!(elements[0].cells(), vec![(0, 0), (0, 1), (1, 0), (1, 1)]);
+        assert_eq!(elements[1].cells(), vec![(0, 2), (0, 3), (1, 2), (1, 3)]);
+    });
+}
+
+#[test]
+fn system_multigrid() {
+    use threed_ice::Solver;
+
+    fn check(system: &mut System, elements: usize) {
+        let power = (0..elements).map(|i| 10.0 * (i + 1) as f64).collect::<Vec<_>>();
+        let profile = [&power[..], &power[..]].concat();
+
+        system.solver = Solver::Direct;
+        let direct = ok!(system.steady_state(&power));
+        let transient = ok!(ok!(Simulator::new(system)).run(&profile));
+
+        system.solver = Solver::Multigrid { tolerance: 1e-14, iterations: 100 };
+        assert::close(&ok!(system.steady_state(&power)), &direct, 1e-6);
+        assert::close(&ok!(ok!(Simulator::new(system)).run(&profile)), &transient, 1e-6);
+    }
+
+    for &name in &["channel_2rm", "channel_4rm", "default", "double"] {
+        setup(Some(name), |path| check(&mut ok!(System::new(path)), 4));
+    }
+
+    setup(Some("grid"), |path| {
+        let mut system = ok!(System::new(path));
+        let dimensions = &system.stack.dimensions;
+        assert_eq!((dimensions.rows(), dimensions.columns()), (16, 16));
+        check(&mut system, 4);
+    });
+}
+
+#[test]
+fn system_output() {
+    setup(None, |path| {
+        let system = ok!(System::new(path));
+        let instructions = &system.output.instructions;
+        assert_eq!(instructions, &vec![
+            OutputInstruction {
+                kind: OutputType::Tmap,
+                element: "DIE1".to_string(),
+                file: "T.txt".to_string(),
+                quantity: OutputQuantity::None,
+                instant: OutputInstant::Slot,
+            },
+            OutputInstruction {
+                kind: OutputType::Pmap,
+                element: "DIE1".to_string(),
+                file: "P.txt".to_string(),
+                quantity: OutputQuantity::None,
+                instant: OutputInstant::Slot,
+            },
+        ]);
+    });
+}
+
+#[test]
+fn system_parser() {
+    use threed_ice::parser::{self, OutputType as Type};
+
+    for name in &["channel_2rm", "channel_4rm", "default", "double", "grid", "pinfin"] {
+        setup(Some(name), |path| {
+            let system = ok!(System::new(path));
+            let stack = ok!(parser::read_stack(path));
+
+            assert_eq!(stack.materials.iter().map(|material| Material {
+                id: material.id.node.clone(),
+                thermal_conductivity: *material.thermal_conductivity,
+                volumetric_heat_capacity: *material.volumetric_heat_capacity,
+            }).collect::<Vec<_>>(), system.stack.materials);
+
+            // In the case of 4RM, the cell length is that of the channels.
+            let dimensions = &system.stack.dimensions;
+            let (cell_length, column) = match stack.channel {
+                Some(ref channel) if *channel.model == ChannelModel::Microchannel4RM => {
+                    (ok!(channel.channel_length.as_ref()).node, 1)
+                },
+                _ => (*stack.dimensions.cell_length, 0),
+            };
+            assert_eq!((*stack.dimensions.chip_length, *stack.dimensions.chip_width),
+                       (dimensions.chip_length(), dimensions.chip_width()));
+            assert_eq!((cell_length, *stack.dimensions.cell_width),
+                       (dimensions.cell_length(column), dimensions.cell_width()));
+
+            let mut elements = system.stack.elements.iter();
+            if let Some(ref other) = stack.heat_sink {
+                let heat_sink = match elements.next() {
+                    Some(&StackElement::HeatSink(ref heat_sink)) => heat_sink,
+                    _ => unreachable!(),
+                };
+                assert_eq!(heat_sink.model, other.model);
+                assert_eq!(heat_sink.heat_transfer_coefficient, *other.heat_transfer_coefficient);
+                assert_eq!(heat_sink.ambient_temperature, *other.ambient_temperature);
+                let parts = heat_sink.sink.iter().zip(&other.sink)
+                                     .chain(heat_sink.spreader.iter().zip(&other.spreader));
+                for (part, other) in parts {
+                    assert_eq!((part.height, part.area), (*other.height, *other.area));
+                    assert_eq!(&part.material.id, &other.material.node);
+                }
+            }
+
+            assert_eq!(stack.elements.len(), elements.len());
+            for (element, other) in elements.zip(&stack.elements) {
+                match (element, &other.node) {
+                    (&StackElement::Channel(ref channel),
+                     &parser::StackElement::Channel { .. }) => {
+                        let other = ok!(stack.channel.as_ref());
+                        assert_eq!(channel.model, *other.model);
+                        assert_eq!(channel.height, *other.height);
+                        assert_eq!(&channel.wall_material.id, &other.wall_material.node);
+                        match (&other.channel_length, &other.pin_diameter, &other.pin_pitch) {
+                            (&Some(ref length), _, _) => assert_eq!(channel.length, **length),
+                            (_, &Some(ref diameter), &Some(ref pitch)) => {
+                                assert!((channel.length - **diameter).abs() < 1e-10);
+                                assert_eq!(channel.pitch, **pitch);
+                            },
+                            _ => unreachable!(),
+                        }
+
+                        let (coolant, other) = (&channel.coolant, &other.coolant);
+                        let value = |value: &Option<parser::Spanned<f64>>| {
+                            value.as_ref().map_or(0.0, |value| value.node)
+                        };
+                        assert_eq!(coolant.flow_rate, value(&other.flow_rate) * 1e12 / 60.0);
+                        assert_eq!(coolant.darcy_velocity, value(&other.darcy_velocity));
+                        assert_eq!(coolant.side_heat_transfer_coefficient,
+                                   value(&other.side_heat_transfer_coefficient));
+                        assert_eq!(coolant.top_heat_transfer_coefficient,
+                                   value(&other.top_heat_transfer_coefficient));
+                        assert_eq!(coolant.bottom_heat_transfer_coefficient,
+                                   value(&other.bottom_heat_transfer_coefficient));
+                        assert_eq!(coolant.volumetric_heat_capacity,
+                                   *other.volumetric_heat_capacity);
+                        assert_eq!(coolant.temperature, *other.temperature);
+                    },
+                    (&StackElement::Die(ref die),
+                     &parser::StackElement::Die { die: ref id, ref floorplan, .. }) => {
+                        assert_eq!(&die.id, &id.node);
+                        let other = ok!(stack.dies.iter().find(|other| other.id.node == die.id));
+                        assert_eq!(die.source, other.source);
+                        let layers = die.layers.iter().map(|layer| {
+                            (layer.height, &layer.material.id)
+                        }).collect::<Vec<_>>();
+                        assert_eq!(layers, other.layers.iter().map(|layer| {
+                            (*layer.height, &layer.material.node)
+                        }).collect::<Vec<_>>());
+
+                        let path = ok!(path.parent()).join(&floorplan.node);
+                        let floorplan = ok!(parser::read_floorplan(path));
+                        assert_eq!(die.floorplan.elements.len(), floorplan.elements.len());
+                        let elements = die.floorplan.elements.iter().zip(&floorplan.elements);
+                        for (element, other) in elements {
+                            assert_eq!(&element.id, &other.id.node);
+                            assert_eq!(element.power, other.power.iter().map(|value| **value)
+                                                                .collect::<Vec<_>>());
+                            assert_eq!(element.rectangles.iter().map(|rectangle| {
+                                (rectangle.x, rectangle.y, rectangle.length, rectangle.width)
+                            }).collect::<Vec<_>>(), other.rectangles.iter().map(|rectangle| {
+                                (*rectangle.x, *rectangle.y, *rectangle.length, *rectangle.width)
+                            }).collect::<Vec<_>>());
+                        }
+                    },
+                    (&StackElement::Layer(ref layer),
+                     &parser::StackElement::Layer { layer: ref id, .. }) => {
+                        let other = ok!(stack.layers.iter().find(|other| {
+                            other.id.as_ref().map(|id| &id.node) == Some(&id.node)
+                        }));
+                        assert_eq!(layer.height, *other.height);
+                        assert_eq!(&layer.material.id, &other.material.node);
+                    },
+                    _ => unreachable!(),
+                }
+            }
+
+            let analysis = &system.analysis;
+            assert_eq!(analysis.kind(), *stack.solver.kind);
+            if let Some(ref step_time) = stack.solver.step_time {
+                assert_eq!(analysis.step_time(), **step_time);
+            }
+            if let Some(ref slot_time) = stack.solver.slot_time {
+                assert_eq!(analysis.slot_time(), **slot_time);
+            }
+            assert_eq!(analysis.initial_temperature(), *stack.solver.initial_temperature);
+
+            assert_eq!(system.output.instructions, stack.output.iter().map(|instruction| {
+                OutputInstruction {
+                    kind: match instruction.kind.node {
+                        Type::Tcell { ref x, ref y } => OutputType::Tcell { x: **x, y: **y },
+                        Type::Tflp => OutputType::Tflp,
+                        Type::Tflpel(ref id) => OutputType::Tflpel(id.node.clone()),
+                        Type::Tmap => OutputType::Tmap,
+                        Type::Pmap => OutputType::Pmap,
+                        Type::Tcoolant => OutputType::Tcoolant,
+                    },
+                    element: instruction.element.node.clone(),
+                    file: instruction.file.node.clone(),
+                    quantity: instruction.quantity.as_ref().map_or(OutputQuantity::None,
+                                                                   |quantity| **quantity),
+                    instant: instruction.instant.as_ref().map_or(OutputInstant::Final,
+                                                                 |instant| **instant),
+                }
+            }).collect::<Vec<_>>());
+        });
+    }
+
+    let description = "material A:\n  thermal conductivity 1;\n  volumetric heat capacity x;";
+    match parser::parse_stack(description) {
+        Err(threed_ice::Error::Syntax(diagnostic)) => {
+            assert_eq!((diagnostic.line, diagnostic.column), (Some(3), Some(28)));
+            assert_eq!(&diagnostic.message, "expected number, found identifier `x`");
+        },
+        _ => unreachable!(),
+    }
+    let description = "A:\n  position 0, 0;\n  dimension 1, 1;\nA:\n  rectangle (1, 0, 1, 1);";
+    match parser::parse_floorplan(description) {
+        Err(threed_ice::Error::Syntax(diagnostic)) => assert_eq!(diagnostic.line, Some(4)),
+        _ => unreachable!(),
+    }
+    match parser::parse_stack("stack:\n  die A B floorplan \"\";") {
+        Err(threed_ice::Error::Syntax(diagnostic)) => {
+            assert_eq!((diagnostic.line, diagnostic.column), (Some(2), Some(21)));
+            assert_eq!(&diagnostic.message, "invalid path \"\"");
+        },
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn system_power_trace() {
+    use matrix::Size;
+
+    setup(Some("double"), |path| {
+        let system = ok!(System::new(path));
+        let trace = ok!(system.power_trace());
+        assert_eq!(trace.rows(), 4);
+        assert_eq!(trace.columns(), 20);
+        assert_eq!(&trace.values[..8], &[10.0, 30.0, 10.0, 30.0, 10.0, 30.0, 10.0, 30.0]);
+        assert_eq!(&trace.values[72..], &[30.0, 10.0, 30.0, 10.0, 30.0, 10.0, 30.0, 10.0]);
+
+        let mut simulator = ok!(Simulator::new(&system));
+        let temperature = ok!(simulator.run(&trace.values));
+        assert_eq!(temperature.len(), 4 * 4 * 4 * 20);
+    });
+}
+
+#[test]
+fn system_solver() {
+    use threed_ice::{Preconditioner, Solver};
+
+    setup(None, |path| {
+        let mut system = ok!(System::new(path));
+        let power = [10.0, 20.0, 30.0, 40.0];
+        let profile = [10.0, 20.0, 30.0, 40.0, 40.0, 30.0, 20.0, 10.0];
+
+        let direct = ok!(system.steady_state(&power));
+        let transient = ok!(ok!(Simulator::new(&system)).run(&profile));
+
+        for &preconditioner in &[Preconditioner::Jacobi, Preconditioner::IncompleteCholesky] {
+            system.solver = Solver::ConjugateGradient {
+                preconditioner: preconditioner,
+                tolerance: 1e-14,
+                iterations: 1000,
+            };
+            assert::close(&ok!(system.steady_state(&power)), &direct, 1e-6);
+            assert::close(&ok!(ok!(Simulator::new(&system)).run(&profile)), &transient, 1e-6);
+        }
+
+        system.solver = Solver::ConjugateGradient {
+            preconditioner: Preconditioner::Jacobi,
+            tolerance: 0.0,
+            iterations: 100,
+        };
+        assert!(system.steady_state(&power).is_err());
+    });
+}
+
 #[test]
 fn system_stack() {
     setup(None, |path| {
@@ -121,21 +898,161 @@ fn system_stack() {
         assert_eq!(dimensions.rows(), 2);
         assert_eq!(dimensions.columns(), 2);
         assert_eq!(dimensions.connections(), 56);
+        assert_eq!(dimensions.nodes(), 16);
+        assert_eq!((dimensions.chip_length(), dimensions.chip_width()), (0.004e6, 0.004e6));
+        assert_eq!((dimensions.cell_length(1), dimensions.cell_width()), (0.002e6, 0.002e6));
+        assert_eq!((0..4).map(|k| dimensions.layer_height(k)).collect::<Vec<_>>(),
+                   &[0.00015e+6, 0.00002e+6, 0.001e+6, 0.0069e+6]);
+
+        let node = dimensions.node(7);
+        assert_eq!(node, Node { layer: 1, row: 1, column: 1 });
+        assert_eq!(dimensions.index(node), 7);
+        assert!((0..16).all(|i| dimensions.index(dimensions.node(i)) == i));
+        assert_eq!(dimensions.center(node), (3000.0, 3000.0, 160.0));
+        assert_eq!(dimensions.size(node), (2000.0, 2000.0, 20.0));
+
+        assert!(stack.layers.is_empty());
+        assert_eq!(stack.materials.iter().map(|material| &material.id).collect::<Vec<_>>(),
+                   &["SILICON", "HEAT_SINK", "HEAT_SPREADER", "INTERFACE_MATERIAL"]);
+        assert_eq!(stack.materials[0].thermal_conductivity, 100e-6);
+        assert_eq!(stack.materials[0].volumetric_heat_capacity, 1.75e-12);
+        assert_eq!(stack.materials[3].thermal_conductivity, 4.0e-6);
 
         let elements = &stack.elements;
         assert_eq!(elements.len(), 2);
-        let die = match (&elements[0], &elements[1]) {
-            (&StackElement::HeatSink, &StackElement::Die(ref die)) => die,
+        let (heat_sink, die) = match (&elements[0], &elements[1]) {
+            (&StackElement::HeatSink(ref heat_sink), &StackElement::Die(ref die)) => {
+                (heat_sink, die)
+            },
             _ => unreachable!(),
         };
+
+        assert_eq!(heat_sink.model, HeatSinkModel::Traditional);
+        assert_eq!(heat_sink.heat_transfer_coefficient, 1.0e-7);
+        assert_eq!(heat_sink.ambient_temperature, 318.15);
+        let sink = ok!(heat_sink.sink.as_ref());
+        assert_eq!((sink.height, sink.area), (0.0069e+6, 0.0036e+12));
+        assert_eq!(&sink.material.id, "HEAT_SINK");
+        let spreader = ok!(heat_sink.spreader.as_ref());
+        assert_eq!((spreader.height, spreader.area), (0.001e+6, 0.0009e+12));
+        assert_eq!(&spreader.material.id, "HEAT_SPREADER");
         assert_eq!(&die.id, "DIE");
+        assert_eq!(die.layers.len(), 2);
+        assert_eq!(die.layers[0].height, 0.00015e+6);
+        assert_eq!(&die.layers[0].material.id, "SILICON");
+        assert_eq!(die.layers[1].height, 0.00002e+6);
+        assert_eq!(&die.layers[1].material.id, "INTERFACE_MATERIAL");
+        assert_eq!(die.source, 0);
+        assert_eq!(die.source_layer(), 0);
+        assert_eq!(die.thermal_layers(), 0..2);
 
         let floorplan = &die.floorplan;
         assert_eq!(floorplan.elements.iter().map(|element| &element.id).collect::<Vec<_>>(),
                    &["Core0", "Core1", "Core2", "Core3"]);
+        assert_eq!(&floorplan.elements[0].power[..5], &[10.0, 10.0, 10.0, 10.0, 15.0]);
+        assert_eq!(floorplan.elements[1].power.len(), 20);
+    });
+}
+
+#[test]
+fn system_state_space() {
+    setup(None, |path| {
+        let mut system = ok!(System::new(path));
+        let (nodes, elements) = (4 * 2 * 2, 4);
+        let power = [10.0, 20.0, 30.0, 40.0];
+        let predict = |model: &StateSpace, state: &[f64], power: &[f64]| {
+            let mut predicted = model.offset.clone();
+            for i in 0..nodes {
+                for j in 0..nodes {
+                    predicted[i] += model.a.values[j * nodes + i] * (state[j] - model.offset[j]);
+                }
+                for j in 0..elements {
+                    predicted[i] += model.b.values[j * nodes + i] * power[j];
+                }
+            }
+            predicted
+        };
+
+        let model = ok!(system.state_space(0.001, Discretization::ZeroOrderHold));
+        assert_eq!(model.nodes(), nodes);
+        assert_eq!(model.elements(), elements);
+        assert::close(&model.offset, &vec![318.15; nodes], 1e-8);
+        ok!(system.analysis.set_transient(1e-6, 1e-3));
+        {
+            let mut simulator = ok!(Simulator::new(&system));
+            ok!(simulator.slot(&power));
+            let state = simulator.temperature().to_vec();
+            assert::close(&predict(&model, &model.offset, &power), &state, 1e-3);
+            ok!(simulator.slot(&[40.0, 0.0, 0.0, 10.0]));
+            assert::close(&predict(&model, &state, &[40.0, 0.0, 0.0, 10.0]),
+                          simulator.temperature(), 1e-3);
+        }
+        ok!(system.analysis.set_transient(0.001, 0.001));
+
+        for j in 0..elements {
+            let sum = (0..nodes).fold(0.0, |sum, i| sum + model.h.values[i * elements + j]);
+            assert::close(&[sum], &[1.0], 1e-12);
+        }
+
+        let model = ok!(system.state_space(0.001, Discretization::BackwardEuler));
+        let mut simulator = ok!(Simulator::new(&system));
+        ok!(simulator.step(&power));
+        assert::close(&predict(&model, &model.offset, &power), simulator.temperature(), 1e-8);
+
+        let path = ok!(path.parent()).join("model.mat");
+        ok!(model.write(&path));
+        let mut content = vec![];
+        ok!(ok!(File::open(&path)).read_to_end(&mut content));
+        let size = |name: &str, count: usize| 5 * 4 + name.len() + 1 + count * 8;
+        assert_eq!(content.len(), size("A", nodes * nodes) + 2 * size("B", nodes * elements) +
+                                  size("offset", nodes) + size("interval", 1));
+        assert_eq!(&content[..22], &[0, 0, 0, 0, 16, 0, 0, 0, 16, 0, 0, 0, 0, 0, 0, 0,
+                                     2, 0, 0, 0, 65, 0]);
+
+        assert!(system.state_space(0.0, Discretization::BackwardEuler).is_err());
+    });
+}
+
+#[test]
+fn system_steady_state() {
+    setup(None, |path| {
+        let system = ok!(System::new(path));
+
+        let temperature = ok!(system.steady_state(&[0.0, 0.0, 0.0, 0.0]));
+        assert::close(&temperature, &vec![318.15; 4 * 2 * 2], 1e-8);
+
+        let temperature = ok!(system.steady_state(&[10.0, 20.0, 30.0, 40.0]));
+        assert_eq!(temperature.len(), 4 * 2 * 2);
+        assert!(temperature.iter().all(|&value| value > 318.15));
+        assert!(temperature[3] > temperature[0]);
+
+        assert!(system.steady_state(&[0.0]).is_err());
     });
 }
 
+#[test]
+fn system_write() {
+    for name in &["channel_2rm", "channel_4rm", "default", "double", "grid", "pinfin"] {
+        setup(Some(name), |path| {
+            let system = ok!(System::new(path));
+            let path = ok!(path.parent()).join("written.stk");
+            ok!(system.write(&path));
+            assert!(path.with_file_name("written_DIE1.flp").exists());
+
+            let written = ok!(System::new(&path));
+            assert_eq!(written.stack.materials, system.stack.materials);
+            assert_eq!(written.stack.elements, system.stack.elements);
+            assert_eq!(written.analysis.kind(), system.analysis.kind());
+            assert_eq!(written.analysis.step_time(), system.analysis.step_time());
+            assert_eq!(written.output.instructions, system.output.instructions);
+            assert_eq!(ok!(written.capacitance()).values, ok!(system.capacitance()).values);
+            assert_eq!(ok!(written.conductance()), ok!(system.conductance()));
+            assert_eq!(ok!(written.distribution()), ok!(system.distribution()));
+            assert_eq!(ok!(written.power_trace()), ok!(system.power_trace()));
+        });
+    }
+}
+
 fn setup<F>(name: Option<&str>, mut code: F) where F: FnMut(&Path) {
     let source = find(name.unwrap_or("default"));
     let directory = ok!(Directory::new("threed_ice"));